@@ -1,48 +1,150 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::{
-    data_types::{Classroom, SimpleDate, StudentGroup, Subject, Teacher},
+    data_types::{
+        DayOverride, LessonLimits, PinnedLesson, PinnedTime, PreviousPlan, SlotTable, StudentGroup,
+    },
+    illegal_state::{IllegalState, IllegalStateObject, IllegalStateSubject},
     input::PlanInput,
-    output::{LessonOwned, PlanOutput},
+    output::{EnergyComponent, EnergyEntry, LessonOwned, PlanOutput, UnplacedLesson},
 };
 
 use super::{
-    annealing_buffer::AnnealingBuffer,
-    illegal_buffer::{CanTeach, IllegalBuffer},
+    annealing_buffer::{AnnealingBuffer, LessonShape},
+    construction::{self, Candidates, LessonOrigin},
+    energy::{
+        BufferStatistics, DayParameters, EnergyParameters, EnergyTerm, PenaltySource,
+        ResolvedTimePreference,
+    },
+    illegal_buffer::{CanHold, CanTeach, IllegalBuffer},
 };
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum AdapterError {
+    UnknownStudentGroup(String),
+    UnknownTeacher(String),
+    UnknownClassroom(String),
+    UnknownSubject(String),
+    NoQualifiedTeacher(String),
+    NoSuitableClassroom { group: String, subject: String },
+    IllegalStateLogicError(IllegalState<String>),
+    InvalidTimeGrid(String),
+    InvalidSoftConstraint(String),
+    InvalidLessonLimits(String),
+    InvalidPinnedLesson(String),
+}
+
+impl std::fmt::Display for AdapterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownStudentGroup(name) => write!(f, "Nieznana grupa studencka: {}", name),
+            Self::UnknownTeacher(name) => write!(f, "Nieznany nauczyciel: {}", name),
+            Self::UnknownClassroom(name) => write!(f, "Nieznana sala: {}", name),
+            Self::UnknownSubject(name) => write!(f, "Nieznany przedmiot: {}", name),
+            Self::NoQualifiedTeacher(name) => {
+                write!(
+                    f,
+                    "Żaden nauczyciel nie może prowadzić przedmiotu: {}",
+                    name
+                )
+            }
+            Self::NoSuitableClassroom { group, subject } => write!(
+                f,
+                "Brak sali, w której grupa {} może mieć zajęcia z przedmiotu: {}",
+                group, subject
+            ),
+            Self::IllegalStateLogicError(state) => {
+                write!(f, "Niedozwolony stan nie ma sensu: {:?}", state)
+            }
+            Self::InvalidTimeGrid(msg) => write!(f, "Niepoprawny podział dnia: {}", msg),
+            Self::InvalidSoftConstraint(msg) => {
+                write!(f, "Niepoprawne ustawienie miękkich ograniczeń: {}", msg)
+            }
+            Self::InvalidLessonLimits(name) => write!(
+                f,
+                "Minimalna liczba zajęć w dniu większa od maksymalnej: {}",
+                name
+            ),
+            Self::InvalidPinnedLesson(msg) => write!(f, "Niepoprawne przypięte zajęcia: {}", msg),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct AnnealingAdapter<'a> {
-    subject_info: HashMap<&'a str, SubjectInfo<'a>>,
+    subject_info: HashMap<&'a str, SubjectInfo>,
     lesson_info: Vec<LessonInfo<'a>>,
     plan_input: Option<&'a PlanInput>,
+    slots: SlotTable,
 }
 
 #[derive(Default)]
-struct SubjectInfo<'a> {
-    pub can_teach: Vec<&'a Teacher>,
-    pub can_hold: Vec<&'a Classroom>,
+struct SubjectInfo {
+    pub can_teach: Vec<u8>,
+    pub can_hold: Vec<u8>,
 }
 
+// Lekcja wspólna (SharedLesson) ma kilka grup, zwykła dokładnie jedną
 struct LessonInfo<'a> {
-    pub student_group: &'a StudentGroup,
+    pub student_groups: Vec<&'a StudentGroup>,
+    pub group_ids: Vec<u8>,
     pub subject_name: &'a str,
+    pub course: usize,
+    // Termin, nauczyciel i sala przypięte w danych wejściowych
+    pub pin: Option<LessonPlacement>,
+    // Stan lekcji w poprzednim planie, jeśli został podany
+    pub previous: Option<LessonPlacement>,
+}
+
+// Termin, nauczyciel i sala lekcji, każde może być nieznane
+#[derive(Default, Clone, Copy, Debug)]
+struct LessonPlacement {
+    pub time: Option<u16>,
+    pub teacher: Option<u8>,
+    pub classroom: Option<u8>,
+}
+
+impl<'a> LessonInfo<'a> {
+    fn students_count(&self) -> i32 {
+        self.student_groups.iter().map(|x| x.size).sum()
+    }
+
+    fn group_names(&self) -> Vec<String> {
+        self.student_groups.iter().map(|x| x.name.clone()).collect()
+    }
 }
 
 impl<'a> AnnealingAdapter<'a> {
-    pub fn of_plan_input(plan_input: &'a PlanInput) -> Self {
-        let mut result: Self = Default::default();
-        result.plan_input = Some(plan_input);
+    pub fn of_plan_input(plan_input: &'a PlanInput) -> Result<Self, AdapterError> {
+        let mut result = Self {
+            plan_input: Some(plan_input),
+            slots: SlotTable::new(
+                &plan_input.time_grid,
+                plan_input.days,
+                plan_input.session_count(),
+            )
+            .map_err(AdapterError::InvalidTimeGrid)?,
+            ..Default::default()
+        };
 
         // Generuj informacje o przedmiotach
         for subj in &plan_input.subjects {
             let can_teach = plan_input
                 .teachers
                 .iter()
-                .filter(|x| x.can_teach.contains(&subj.name))
+                .enumerate()
+                .filter(|(_, x)| x.can_teach.contains(&subj.name))
+                .map(|(teacher_id, _)| teacher_id as u8)
                 .collect::<Vec<_>>();
 
-            let can_hold = plan_input.classrooms.iter().collect::<Vec<_>>(); // TODO: Filtruj po pojemności
+            // Pojemność sal zależy od grupy, więc jest sprawdzana w `lesson_classrooms`
+            let can_hold = plan_input
+                .classrooms
+                .iter()
+                .enumerate()
+                .filter(|(_, x)| x.can_hold_subject(subj))
+                .map(|(classroom_id, _)| classroom_id as u8)
+                .collect::<Vec<_>>();
 
             result.subject_info.insert(
                 subj.name.as_ref(),
@@ -53,93 +155,1156 @@ impl<'a> AnnealingAdapter<'a> {
             );
         }
 
-        // Generuj informacje o zajęciach
-        for student_group in &plan_input.student_groups {
+        // Generuj informacje o zajęciach, każdy przedmiot `count` razy w zjeździe
+        // albo tyle razy, ile wynika z liczby godzin w semestrze. Nieznany
+        // przedmiot dostaje jedną lekcję, żeby create_illegal_buffer mógł
+        // zgłosić błąd.
+        let mut course = 0;
+        for (group_id, student_group) in plan_input.student_groups.iter().enumerate() {
             for subject_name in &student_group.subjects {
+                let count = plan_input
+                    .find_subject(subject_name)
+                    .map_or(1, |x| plan_input.lesson_count(x));
+                for _ in 0..count {
+                    result.lesson_info.push(LessonInfo {
+                        student_groups: vec![student_group],
+                        group_ids: vec![group_id as u8],
+                        subject_name,
+                        course,
+                        pin: None,
+                        previous: None,
+                    })
+                }
+                course += 1;
+            }
+        }
+
+        for shared_lesson in &plan_input.shared_lessons {
+            let group_ids = shared_lesson
+                .groups
+                .iter()
+                .map(|x| result.group_index(x))
+                .collect::<Result<Vec<_>, _>>()?;
+            let student_groups = group_ids
+                .iter()
+                .map(|&x| &plan_input.student_groups[x as usize])
+                .collect::<Vec<_>>();
+            let count = plan_input
+                .find_subject(&shared_lesson.subject)
+                .map_or(1, |x| plan_input.lesson_count(x));
+            for _ in 0..count {
                 result.lesson_info.push(LessonInfo {
-                    student_group,
-                    subject_name,
+                    student_groups: student_groups.clone(),
+                    group_ids: group_ids.clone(),
+                    subject_name: &shared_lesson.subject,
+                    course,
+                    pin: None,
+                    previous: None,
+                })
+            }
+            course += 1;
+        }
+
+        for pinned_lesson in &plan_input.pinned_lessons {
+            result.pin_lesson(pinned_lesson)?;
+        }
+
+        Ok(result)
+    }
+
+    fn pin_lesson(&mut self, pinned_lesson: &PinnedLesson) -> Result<(), AdapterError> {
+        let describe = || {
+            format!(
+                "{} ({})",
+                pinned_lesson.subject,
+                pinned_lesson.groups.join("+")
+            )
+        };
+        let mut group_ids = pinned_lesson
+            .groups
+            .iter()
+            .map(|x| self.group_index(x))
+            .collect::<Result<Vec<_>, _>>()?;
+        group_ids.sort_unstable();
+
+        let pin = LessonPlacement {
+            time: pinned_lesson
+                .time
+                .map(|x| {
+                    self.pinned_time(x).ok_or_else(|| {
+                        AdapterError::InvalidPinnedLesson(format!(
+                            "{}: brak zajęć o {} w dniu {} zjazdu {}",
+                            describe(),
+                            String::from(x.start),
+                            x.day,
+                            x.session
+                        ))
+                    })
                 })
+                .transpose()?,
+            teacher: pinned_lesson
+                .teacher
+                .as_ref()
+                .map(|x| self.teacher_index(x))
+                .transpose()?,
+            classroom: pinned_lesson
+                .classroom
+                .as_ref()
+                .map(|x| self.classroom_index(x))
+                .transpose()?,
+        };
+
+        let lesson_info = self
+            .lesson_info
+            .iter_mut()
+            .find(|x| {
+                let mut lesson_groups = x.group_ids.clone();
+                lesson_groups.sort_unstable();
+                x.pin.is_none()
+                    && x.subject_name == pinned_lesson.subject
+                    && lesson_groups == group_ids
+            })
+            .ok_or_else(|| {
+                AdapterError::InvalidPinnedLesson(format!(
+                    "{}: brak zajęć do przypięcia",
+                    describe()
+                ))
+            })?;
+        lesson_info.pin = Some(pin);
+        Ok(())
+    }
+
+    // Przypisuje lekcjom ich stan z poprzedniego planu, szukając ich po nazwach
+    // przedmiotu i grup. Nauczyciele, sale i terminy, których już nie ma, są
+    // pomijane. Zwraca liczbę odnalezionych lekcji.
+    pub fn warm_start(&mut self, previous_plan: &PreviousPlan) -> usize {
+        let mut found = 0;
+        for previous_lesson in &previous_plan.lessons {
+            let group_ids = previous_lesson
+                .groups
+                .iter()
+                .map(|x| self.group_index(x))
+                .collect::<Result<Vec<_>, _>>();
+            let mut group_ids = match group_ids {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            group_ids.sort_unstable();
+
+            let placement = LessonPlacement {
+                time: self.pinned_time(PinnedTime {
+                    session: previous_lesson.session,
+                    day: previous_lesson.day,
+                    start: previous_lesson.start,
+                }),
+                teacher: self.teacher_index(&previous_lesson.teacher).ok(),
+                classroom: self.classroom_index(&previous_lesson.classroom).ok(),
+            };
+
+            let lesson_info = self.lesson_info.iter_mut().find(|x| {
+                let mut lesson_groups = x.group_ids.clone();
+                lesson_groups.sort_unstable();
+                x.previous.is_none()
+                    && x.subject_name == previous_lesson.subject_name
+                    && lesson_groups == group_ids
+            });
+            if let Some(lesson_info) = lesson_info {
+                lesson_info.previous = Some(placement);
+                found += 1;
             }
         }
+        found
+    }
 
-        result
+    fn pinned_time(&self, pinned_time: PinnedTime) -> Option<u16> {
+        (0..self.slots.len() as u16).find(|&time| {
+            let slot = self.slots.slot(time);
+            slot.session == pinned_time.session
+                && slot.day == pinned_time.day
+                && slot.start == pinned_time.start
+        })
     }
 
-    pub fn create_annealing_buffer(&self) -> AnnealingBuffer {
+    fn lesson_shapes(&self) -> Vec<LessonShape> {
         let plan_input = self.plan_input.unwrap();
+        self.lesson_info
+            .iter()
+            .map(|x| {
+                // Nieznany przedmiot zostanie zgłoszony przez create_illegal_buffer
+                let duration = plan_input
+                    .find_subject(x.subject_name)
+                    .map_or(1, |x| x.duration.max(1));
+                LessonShape::new(x.group_ids.clone(), duration, x.course)
+            })
+            .collect()
+    }
 
-        let lesson_count = plan_input
-            .student_groups
+    pub fn create_annealing_buffer(&self, illegal_buffer: &IllegalBuffer) -> AnnealingBuffer {
+        let mut buffer = AnnealingBuffer::new(self.lesson_shapes(), self.slots.clone());
+        let max_time = buffer.max_time;
+
+        buffer.lesson_teachers = self
+            .lesson_info
             .iter()
-            .map(|x| x.subjects.len())
-            .reduce(|a, b| a + b)
-            .unwrap();
+            .map(|x| self.lesson_teachers(x))
+            .collect();
 
-        let max_time = plan_input.days * 6; // od 0 = 8:00 do 5 = 18:00
-        let mut buffer = AnnealingBuffer::new(lesson_count, max_time);
-
-        let mut lesson_index: usize = 0;
-
-        for (group_index, _group) in plan_input.student_groups.iter().enumerate() {
-            'lesson: for _subject in &_group.subjects {
-                for time in 0..max_time {
-                    for (teacher_index, _teacher) in plan_input.teachers.iter().enumerate() {
-                        for (classroom_index, _classroom) in
-                            plan_input.classrooms.iter().enumerate()
-                        {
-                            // TODO: sprawdzaj czy lekcja może się odbyć w sali
-                            if buffer.place_lesson(
-                                lesson_index,
-                                teacher_index as u8,
-                                classroom_index as u8,
-                                time,
-                                group_index as u8,
-                            ) {
-                                lesson_index += 1;
-                                continue 'lesson;
-                            }
-                        }
-                    }
+        buffer.lesson_classrooms = self
+            .lesson_info
+            .iter()
+            .map(|x| self.lesson_classrooms(x))
+            .collect();
+
+        // Przypięte lekcje są umieszczane w swoim terminie, a lekcje z poprzedniego
+        // planu możliwie w tym samym stanie. Kolejne lekcje kursu są umieszczane od
+        // początku kolejnych zjazdów, żeby od razu były rozłożone na cały semestr.
+        let session_length = max_time / self.slots.sessions() as u16;
+        let mut course_lessons = HashMap::new();
+        let candidates = self
+            .lesson_info
+            .iter()
+            .enumerate()
+            .map(|(lesson_index, lesson_info)| {
+                let pinned_time = lesson_info.pin.and_then(|x| x.time);
+                if let Some(time) = pinned_time {
+                    buffer.inner_state.pin_time(lesson_index, time);
                 }
-            }
-        }
 
-        buffer.max_time = max_time;
-        buffer.classroom_count = plan_input.classrooms.len() as u8;
+                let previous = lesson_info.previous.unwrap_or_default();
+                let placed_in_course = course_lessons.entry(lesson_info.course).or_insert(0);
+                let first_time = previous
+                    .time
+                    .unwrap_or((*placed_in_course % self.slots.sessions() as u16) * session_length);
+                *placed_in_course += 1;
+
+                Candidates {
+                    times: match pinned_time {
+                        Some(time) => vec![time],
+                        None => (0..max_time)
+                            .map(|x| ((x as u32 + first_time as u32) % max_time as u32) as u16)
+                            .collect(),
+                    },
+                    teachers: preferred_first(
+                        &buffer.lesson_teachers[lesson_index],
+                        previous.teacher,
+                    ),
+                    classrooms: preferred_first(
+                        &buffer.lesson_classrooms[lesson_index],
+                        previous.classroom,
+                    ),
+                    origin: if lesson_info.pin.is_some() {
+                        LessonOrigin::Pinned
+                    } else if lesson_info.previous.is_some() {
+                        LessonOrigin::Previous
+                    } else {
+                        LessonOrigin::New
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        construction::construct(&mut buffer, illegal_buffer, &candidates);
 
         buffer
     }
 
-    pub fn create_illegal_buffer(&self) -> IllegalBuffer {
+    pub fn create_illegal_buffer(&self) -> Result<IllegalBuffer, AdapterError> {
         let mut can_teach = HashSet::new();
         let mut can_hold = HashSet::new();
-        let mut illegal_states = Vec::new();
 
         for (lesson_id, lesson_info) in self.lesson_info.iter().enumerate() {
-            let can_teach = &self.subject_info[lesson_info.subject_name].can_teach;
+            if !self.subject_info.contains_key(lesson_info.subject_name) {
+                return Err(AdapterError::UnknownSubject(
+                    lesson_info.subject_name.to_owned(),
+                ));
+            }
+
+            let lesson_teachers = self.lesson_teachers(lesson_info);
+            if lesson_teachers.is_empty() {
+                return Err(AdapterError::NoQualifiedTeacher(
+                    lesson_info.subject_name.to_owned(),
+                ));
+            }
+
+            for teacher_id in lesson_teachers {
+                can_teach.insert(CanTeach {
+                    lesson_id,
+                    teacher_id,
+                });
+            }
+
+            let lesson_classrooms = self.lesson_classrooms(lesson_info);
+            if lesson_classrooms.is_empty() {
+                return Err(AdapterError::NoSuitableClassroom {
+                    group: lesson_info.group_names().join("+"),
+                    subject: lesson_info.subject_name.to_owned(),
+                });
+            }
+
+            for classroom_id in lesson_classrooms {
+                can_hold.insert(CanHold {
+                    lesson_id,
+                    classroom_id,
+                });
+            }
         }
 
-        IllegalBuffer::new(can_teach, can_hold, illegal_states)
+        let illegal_states = self
+            .plan_input
+            .unwrap()
+            .illegal_states
+            .iter()
+            .map(|x| self.resolve_illegal_state(x))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(IllegalBuffer::new(
+            can_teach,
+            can_hold,
+            illegal_states,
+            self.lesson_shapes(),
+            self.slots.clone(),
+        ))
+    }
+
+    // Nauczyciele, którzy mogą prowadzić daną lekcję
+    // Przypięty nauczyciel zastępuje wszystkie inne możliwości
+    fn lesson_teachers(&self, lesson_info: &LessonInfo) -> Vec<u8> {
+        match lesson_info.pin.and_then(|x| x.teacher) {
+            Some(teacher) => vec![teacher],
+            None => self.subject_info[lesson_info.subject_name]
+                .can_teach
+                .clone(),
+        }
+    }
+
+    // Sale, w których może odbyć się dana lekcja
+    // Przypięta sala zastępuje wszystkie inne możliwości
+    fn lesson_classrooms(&self, lesson_info: &LessonInfo) -> Vec<u8> {
+        if let Some(classroom) = lesson_info.pin.and_then(|x| x.classroom) {
+            return vec![classroom];
+        }
+        let classrooms = &self.plan_input.unwrap().classrooms;
+        self.subject_info[lesson_info.subject_name]
+            .can_hold
+            .iter()
+            .copied()
+            .filter(|&x| classrooms[x as usize].capacity >= lesson_info.students_count())
+            .collect()
+    }
+
+    // Zamienia nazwy w niedozwolonym stanie z danych wejściowych na indeksy
+    fn resolve_illegal_state(
+        &self,
+        illegal_state: &IllegalState<String>,
+    ) -> Result<IllegalState, AdapterError> {
+        if illegal_state.is_logic_error() {
+            return Err(AdapterError::IllegalStateLogicError(illegal_state.clone()));
+        }
+
+        let subject = self.resolve_subject(&illegal_state.subject)?;
+
+        let object = match &illegal_state.object {
+            IllegalStateObject::StudentGroup(name) => {
+                IllegalStateObject::StudentGroup(self.group_index(name)?)
+            }
+            IllegalStateObject::Teacher(name) => {
+                IllegalStateObject::Teacher(self.teacher_index(name)?)
+            }
+            IllegalStateObject::Classroom(name) => {
+                IllegalStateObject::Classroom(self.classroom_index(name)?)
+            }
+            IllegalStateObject::Day(day) => IllegalStateObject::Day(*day),
+            IllegalStateObject::Session(session) => IllegalStateObject::Session(*session),
+            IllegalStateObject::DayHour(date) => IllegalStateObject::DayHour(*date),
+        };
+
+        Ok(IllegalState { subject, object })
+    }
+
+    fn resolve_subject(
+        &self,
+        subject: &IllegalStateSubject<String>,
+    ) -> Result<IllegalStateSubject, AdapterError> {
+        Ok(match subject {
+            IllegalStateSubject::StudentGroup(name) => {
+                IllegalStateSubject::StudentGroup(self.group_index(name)?)
+            }
+            IllegalStateSubject::Teacher(name) => {
+                IllegalStateSubject::Teacher(self.teacher_index(name)?)
+            }
+            IllegalStateSubject::Classroom(name) => {
+                IllegalStateSubject::Classroom(self.classroom_index(name)?)
+            }
+        })
+    }
+
+    fn group_index(&self, name: &str) -> Result<u8, AdapterError> {
+        let plan_input = self.plan_input.unwrap();
+        plan_input
+            .student_groups
+            .iter()
+            .position(|x| x.name == name)
+            .map(|x| x as u8)
+            .ok_or_else(|| AdapterError::UnknownStudentGroup(name.to_owned()))
+    }
+
+    fn teacher_index(&self, name: &str) -> Result<u8, AdapterError> {
+        let plan_input = self.plan_input.unwrap();
+        plan_input
+            .teachers
+            .iter()
+            .position(|x| x.name == name)
+            .map(|x| x as u8)
+            .ok_or_else(|| AdapterError::UnknownTeacher(name.to_owned()))
+    }
+
+    fn classroom_index(&self, name: &str) -> Result<u8, AdapterError> {
+        let plan_input = self.plan_input.unwrap();
+        plan_input
+            .classrooms
+            .iter()
+            .position(|x| x.name == name)
+            .map(|x| x as u8)
+            .ok_or_else(|| AdapterError::UnknownClassroom(name.to_owned()))
     }
 
     pub fn buffer_to_output(&self, annealing_buffer: &AnnealingBuffer) -> PlanOutput {
+        let plan_input = self.plan_input.unwrap();
         let mut output = PlanOutput::new();
         let state_ref = annealing_buffer.inner_state.state_ref();
         for (lesson_id, lesson) in state_ref.lessons.iter().enumerate() {
             let lesson_info = &self.lesson_info[lesson_id];
+            if !state_ref.placed[lesson_id] {
+                output.push_unplaced_lesson(UnplacedLesson {
+                    groups: lesson_info.group_names(),
+                    subject_name: lesson_info.subject_name.to_owned(),
+                });
+                continue;
+            }
+            let duration = state_ref.lesson_shapes[lesson_id].duration;
+            let slot = self.slots.slot(lesson.time);
             output.push_lesson(LessonOwned {
                 subject_name: lesson_info.subject_name.to_owned(),
-                group: lesson_info.student_group.name.clone(),
-                teacher: self.plan_input.unwrap().teachers[lesson.teacher as usize]
-                    .name
-                    .clone(),
-                time: SimpleDate::from_u8_time(lesson.time),
-                classroom: self.plan_input.unwrap().classrooms[lesson.classroom as usize]
+                groups: lesson_info.group_names(),
+                teacher: plan_input.teachers[lesson.teacher as usize].name.clone(),
+                session: slot.session,
+                day: slot.day,
+                weekday: plan_input.weekday_of(slot.session, slot.day),
+                date: plan_input.date_of(slot.session, slot.day),
+                start: slot.start,
+                end: self.slots.end(lesson.time, duration),
+                duration,
+                pinned: state_ref.pinned_time[lesson_id],
+                classroom: plan_input.classrooms[lesson.classroom as usize]
                     .name
                     .clone(),
             })
         }
         output
     }
+
+    // Wagi energii z danych wejściowych, z nazwami grup i nauczycieli zamienionymi
+    // na indeksy. Wagi muszą być skończone i nieujemne.
+    pub fn energy_parameters(&self) -> Result<EnergyParameters, AdapterError> {
+        let plan_input = self.plan_input.unwrap();
+        let soft_constraints = &plan_input.soft_constraints;
+        let check_weight = |name: &str, weight: f32| {
+            if weight.is_finite() && weight >= 0.0 {
+                Ok(weight)
+            } else {
+                Err(AdapterError::InvalidSoftConstraint(format!(
+                    "waga {} = {}",
+                    name, weight
+                )))
+            }
+        };
+
+        let weights = soft_constraints.weights.clone();
+        for (name, weight) in weights.named().iter() {
+            check_weight(name, *weight)?;
+        }
+        let mut parameters = EnergyParameters::new(weights, soft_constraints.ideal_lessons_in_day);
+        parameters.group_count = plan_input.student_groups.len();
+        parameters.teacher_count = plan_input.teachers.len();
+
+        let resolve = |name: &str, day_override: &DayOverride, default: DayParameters| {
+            Ok(DayParameters {
+                ideal_lessons_in_day: day_override
+                    .ideal_lessons_in_day
+                    .unwrap_or(default.ideal_lessons_in_day),
+                gap_weight: check_weight(
+                    &format!("{}.gap_weight", name),
+                    day_override.gap_weight.unwrap_or(default.gap_weight),
+                )?,
+                lessons_in_day_weight: check_weight(
+                    &format!("{}.lessons_in_day_weight", name),
+                    day_override
+                        .lessons_in_day_weight
+                        .unwrap_or(default.lessons_in_day_weight),
+                )?,
+            })
+        };
+        for (name, day_override) in &soft_constraints.groups {
+            let group = self.group_index(name)?;
+            let day = resolve(name, day_override, parameters.group(group))?;
+            parameters.group_overrides.insert(group, day);
+        }
+        for (name, day_override) in &soft_constraints.teachers {
+            let teacher = self.teacher_index(name)?;
+            let day = resolve(name, day_override, parameters.teacher(teacher))?;
+            parameters.teacher_overrides.insert(teacher, day);
+        }
+
+        for (id, preference) in soft_constraints.time_preferences.iter().enumerate() {
+            let penalized = (0..self.slots.len() as u16)
+                .map(|time| {
+                    let slot = self.slots.slot(time);
+                    let weekday = plan_input.weekday_of(slot.session, slot.day);
+                    preference.penalizes(slot, weekday)
+                })
+                .collect();
+            parameters.time_preferences.push(ResolvedTimePreference {
+                subject: self.resolve_subject(&preference.subject)?,
+                penalized,
+                weight: check_weight(&format!("time_preferences[{}]", id), preference.weight)?,
+            });
+        }
+
+        parameters.previous_times = self
+            .lesson_info
+            .iter()
+            .map(|x| x.previous.and_then(|x| x.time))
+            .collect();
+
+        // Twarde ograniczenia liczby zajęć
+        let hard_constraints = &plan_input.hard_constraints;
+        let check_limits = |name: &str, limits: LessonLimits| match limits {
+            LessonLimits {
+                min_lessons_in_day: Some(min),
+                max_lessons_in_day: Some(max),
+                ..
+            } if min > max => Err(AdapterError::InvalidLessonLimits(name.to_owned())),
+            _ => Ok(limits),
+        };
+        parameters.group_limits = check_limits("grupy", hard_constraints.group_limits)?;
+        parameters.teacher_limits = check_limits("nauczyciele", hard_constraints.teacher_limits)?;
+        for (name, limits) in &hard_constraints.groups {
+            let limits = check_limits(name, limits.or(parameters.group_limits))?;
+            parameters
+                .group_limit_overrides
+                .insert(self.group_index(name)?, limits);
+        }
+        for (name, limits) in &hard_constraints.teachers {
+            let limits = check_limits(name, limits.or(parameters.teacher_limits))?;
+            parameters
+                .teacher_limit_overrides
+                .insert(self.teacher_index(name)?, limits);
+        }
+
+        Ok(parameters)
+    }
+
+    // Składowe energii z nazwami grup, nauczycieli i przedmiotów zamiast indeksów
+    pub fn energy_report(&self, statistics: &BufferStatistics) -> Vec<EnergyComponent> {
+        self.report(statistics.breakdown())
+    }
+
+    // Naruszenia ograniczeń liczby zajęć w dniu i w zjeździe
+    pub fn hard_violation_report(&self, statistics: &BufferStatistics) -> Vec<EnergyComponent> {
+        self.report(statistics.hard_breakdown())
+    }
+
+    fn report(&self, terms: Vec<EnergyTerm>) -> Vec<EnergyComponent> {
+        let plan_input = self.plan_input.unwrap();
+        let days = plan_input.days as u16;
+        let session_day = |day: u16| (Some((day / days) as u8), Some((day % days) as u8));
+
+        terms
+            .into_iter()
+            .map(|term| EnergyComponent {
+                name: term.name.to_owned(),
+                weight: term.weight,
+                total: term.raw,
+                energy: term.energy,
+                entries: term
+                    .entries
+                    .iter()
+                    .map(|penalty| {
+                        let mut entry = EnergyEntry {
+                            penalty: penalty.raw,
+                            energy: penalty.energy,
+                            ..Default::default()
+                        };
+                        match penalty.source {
+                            PenaltySource::GroupDay { group, day } => {
+                                entry.groups =
+                                    vec![plan_input.student_groups[group as usize].name.clone()];
+                                (entry.session, entry.day) = session_day(day);
+                            }
+                            PenaltySource::TeacherDay { teacher, day } => {
+                                entry.teacher =
+                                    Some(plan_input.teachers[teacher as usize].name.clone());
+                                (entry.session, entry.day) = session_day(day);
+                            }
+                            PenaltySource::GroupSession { group, session } => {
+                                entry.groups =
+                                    vec![plan_input.student_groups[group as usize].name.clone()];
+                                entry.session = Some(session);
+                            }
+                            PenaltySource::TeacherSession { teacher, session } => {
+                                entry.teacher =
+                                    Some(plan_input.teachers[teacher as usize].name.clone());
+                                entry.session = Some(session);
+                            }
+                            PenaltySource::Course(course) => {
+                                let lesson_info = self
+                                    .lesson_info
+                                    .iter()
+                                    .find(|x| x.course == course)
+                                    .unwrap();
+                                entry.groups = lesson_info.group_names();
+                                entry.subject_name = Some(lesson_info.subject_name.to_owned());
+                            }
+                            PenaltySource::MovedLesson(lesson_id) => {
+                                // Termin, z którego lekcja została przesunięta
+                                let lesson_info = &self.lesson_info[lesson_id];
+                                entry.groups = lesson_info.group_names();
+                                entry.subject_name = Some(lesson_info.subject_name.to_owned());
+                                if let Some(time) = lesson_info.previous.and_then(|x| x.time) {
+                                    let slot = self.slots.slot(time);
+                                    entry.session = Some(slot.session);
+                                    entry.day = Some(slot.day);
+                                }
+                            }
+                            PenaltySource::TimePreference(id) => {
+                                let preference = &plan_input.soft_constraints.time_preferences[id];
+                                match &preference.subject {
+                                    IllegalStateSubject::StudentGroup(name) => {
+                                        entry.groups = vec![name.clone()]
+                                    }
+                                    IllegalStateSubject::Teacher(name) => {
+                                        entry.teacher = Some(name.clone())
+                                    }
+                                    IllegalStateSubject::Classroom(name) => {
+                                        entry.classroom = Some(name.clone())
+                                    }
+                                }
+                                entry.time_preference = Some(id);
+                            }
+                        }
+                        entry
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+// Kopia `values` z wartością `preferred` (jeśli występuje) na początku
+fn preferred_first(values: &[u8], preferred: Option<u8>) -> Vec<u8> {
+    let mut result = values.to_vec();
+    if let Some(index) = preferred.and_then(|x| values.iter().position(|&y| y == x)) {
+        result[..=index].rotate_right(1);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{
+        CalendarDate, Classroom, ClockTime, PreferenceKind, PreviousLesson, Session, SharedLesson,
+        StudentGroup, Subject, SubjectType, Teacher, TimePreference, Weekday,
+    };
+
+    fn plan_input() -> PlanInput {
+        PlanInput::new(
+            vec![StudentGroup {
+                name: "11A".to_owned(),
+                size: 15,
+                subjects: vec!["matematyka".to_owned(), "WF".to_owned()],
+            }],
+            vec![
+                Teacher::new("Kowalski".to_owned(), vec!["matematyka".to_owned()]),
+                Teacher::new("Nowak".to_owned(), vec!["WF".to_owned()]),
+            ],
+            vec![Classroom::new("3.15d".to_owned(), 40)],
+            Vec::new(),
+            vec![
+                Subject::new("matematyka".to_owned(), SubjectType::Cwiczenia, 1),
+                Subject::new("WF".to_owned(), SubjectType::Cwiczenia, 1),
+            ],
+            1,
+        )
+    }
+
+    #[test]
+    fn lessons_are_placed_with_qualified_teachers() {
+        let plan_input = plan_input();
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+
+        let output = adapter.buffer_to_output(&buffer);
+        for lesson in &output.lessons {
+            let teacher = plan_input
+                .teachers
+                .iter()
+                .find(|x| x.name == lesson.teacher)
+                .unwrap();
+            assert!(teacher.can_teach.contains(&lesson.subject_name));
+        }
+    }
+
+    #[test]
+    fn lessons_are_placed_in_classrooms_large_enough() {
+        let mut plan_input = plan_input();
+        plan_input.student_groups[0].size = 30;
+        plan_input
+            .classrooms
+            .insert(0, Classroom::new("lab".to_owned(), 12));
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+
+        assert_eq!(buffer.lesson_classrooms, vec![vec![1], vec![1]]);
+        let output = adapter.buffer_to_output(&buffer);
+        assert!(output.lessons.iter().all(|x| x.classroom == "3.15d"));
+    }
+
+    #[test]
+    fn lesson_count_equals_sum_of_subject_counts() {
+        let mut plan_input = plan_input();
+        plan_input.subjects[0].count = 3;
+        plan_input.subjects[1].count = 2;
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+        buffer.assert_maps_synchronized("lesson_count_equals_sum_of_subject_counts");
+
+        let output = adapter.buffer_to_output(&buffer);
+        assert_eq!(output.len(), 5);
+        assert_eq!(
+            output
+                .lessons
+                .iter()
+                .filter(|x| x.subject_name == "matematyka")
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn labs_are_placed_only_in_lab_rooms() {
+        let mut plan_input = plan_input();
+        plan_input.subjects[0].subject_type = SubjectType::Laboratorium;
+        plan_input.subjects[0].requires = vec!["komputery".to_owned()];
+        plan_input.classrooms[0].kinds = vec![SubjectType::Cwiczenia];
+
+        let mut lab = Classroom::new("lab".to_owned(), 40);
+        lab.kinds = vec![SubjectType::Laboratorium];
+        plan_input.classrooms.push(lab);
+
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        assert!(adapter.create_illegal_buffer().is_err());
+
+        plan_input.classrooms[1].equipment = vec!["komputery".to_owned()];
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+        assert_eq!(buffer.lesson_classrooms, vec![vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn shared_lesson_uses_combined_group_size() {
+        let mut plan_input = plan_input();
+        plan_input.student_groups.push(StudentGroup {
+            name: "11B".to_owned(),
+            size: 15,
+            subjects: Vec::new(),
+        });
+        plan_input
+            .classrooms
+            .insert(0, Classroom::new("mała".to_owned(), 20));
+        plan_input.shared_lessons.push(SharedLesson {
+            subject: "matematyka".to_owned(),
+            groups: vec!["11A".to_owned(), "11B".to_owned()],
+        });
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+        buffer.assert_maps_synchronized("shared_lesson_uses_combined_group_size");
+
+        assert_eq!(buffer.lesson_classrooms[2], vec![1]);
+        let output = adapter.buffer_to_output(&buffer);
+        assert_eq!(output.lessons[2].groups, vec!["11A", "11B"]);
+    }
+
+    #[test]
+    fn output_has_clock_times_and_calendar_dates() {
+        let mut plan_input = plan_input();
+        plan_input.subjects[0].duration = 2;
+        plan_input.start_date = Some(CalendarDate::new(2021, 10, 2));
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+
+        let output = adapter.buffer_to_output(&buffer);
+        let lesson = &output.lessons[0];
+        assert_eq!(lesson.weekday, Weekday::Sobota);
+        assert_eq!(lesson.date, Some(CalendarDate::new(2021, 10, 2)));
+        assert_eq!(lesson.start, ClockTime::new(8, 0));
+        assert_eq!(lesson.end, ClockTime::new(12, 0));
+        assert_eq!(output.lessons[1].start, ClockTime::new(12, 0));
+
+        plan_input.start_date = None;
+        plan_input.first_weekday = Weekday::Piatek;
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let output = adapter.buffer_to_output(&buffer);
+        assert_eq!(output.lessons[0].weekday, Weekday::Piatek);
+        assert_eq!(output.lessons[0].date, None);
+    }
+
+    #[test]
+    fn semester_hours_are_spread_across_sessions() {
+        let mut plan_input = plan_input();
+        plan_input.sessions = vec![
+            Session {
+                start_date: CalendarDate::new(2021, 10, 2),
+            },
+            Session {
+                start_date: CalendarDate::new(2021, 10, 16),
+            },
+        ];
+        // 8 godzin po 45 minut to 3 zajęcia po 120 minut
+        plan_input.subjects[0].semester_hours = Some(8);
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+        buffer.assert_maps_synchronized("semester_hours_are_spread_across_sessions");
+
+        let output = adapter.buffer_to_output(&buffer);
+        let math = output
+            .lessons
+            .iter()
+            .filter(|x| x.subject_name == "matematyka")
+            .collect::<Vec<_>>();
+        assert_eq!(math.len(), 3);
+        assert_eq!(math.iter().filter(|x| x.session == 1).count(), 1);
+        assert_eq!(math[1].date, Some(CalendarDate::new(2021, 10, 16)));
+
+        // Przedmiot bez liczby godzin ma `count` zajęć w każdym zjeździe
+        assert_eq!(
+            output
+                .lessons
+                .iter()
+                .filter(|x| x.subject_name == "WF")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn lessons_without_free_slot_are_hard_violations() {
+        let mut plan_input = plan_input();
+        plan_input.subjects[0].count = 7;
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+        buffer.assert_maps_synchronized("lessons_without_free_slot_are_hard_violations");
+
+        let mut statistics = BufferStatistics::new(Default::default());
+        statistics.emplace_of_buffer(&buffer);
+        assert_eq!(statistics.hard_violations(), 2);
+
+        let output = adapter.buffer_to_output(&buffer);
+        assert_eq!(output.len(), 6);
+        assert_eq!(output.unplaced_lessons.len(), 2);
+        assert_eq!(output.unplaced_lessons[1].subject_name, "WF");
+    }
+
+    #[test]
+    fn energy_report_names_offending_groups_and_teachers() {
+        let mut plan_input = plan_input();
+        plan_input
+            .soft_constraints
+            .weights
+            .group_lessons_in_day_weight = 2.0;
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+        let mut statistics = BufferStatistics::new(adapter.energy_parameters().unwrap());
+        statistics.emplace_of_buffer(&buffer);
+
+        let report = adapter.energy_report(&statistics);
+        let group_day = report
+            .iter()
+            .find(|x| x.name == "group_lessons_in_day")
+            .unwrap();
+        // Dwie lekcje zamiast czterech
+        assert_eq!(group_day.total, 2);
+        assert_eq!(group_day.energy, 4.0);
+        assert_eq!(group_day.entries[0].groups, vec!["11A"]);
+        assert_eq!(group_day.entries[0].day, Some(0));
+        assert_eq!(group_day.entries[0].energy, 4.0);
+
+        let teacher_day = report
+            .iter()
+            .find(|x| x.name == "teacher_lessons_in_day")
+            .unwrap();
+        let mut teachers = teacher_day
+            .entries
+            .iter()
+            .map(|x| x.teacher.clone().unwrap())
+            .collect::<Vec<_>>();
+        teachers.sort();
+        assert_eq!(teachers, vec!["Kowalski", "Nowak"]);
+    }
+
+    #[test]
+    fn soft_constraint_overrides_are_resolved_by_name() {
+        let mut plan_input = plan_input();
+        plan_input.soft_constraints.ideal_lessons_in_day = 3;
+        plan_input.soft_constraints.groups.insert(
+            "11A".to_owned(),
+            DayOverride {
+                ideal_lessons_in_day: Some(2),
+                ..Default::default()
+            },
+        );
+        plan_input.soft_constraints.teachers.insert(
+            "Nowak".to_owned(),
+            DayOverride {
+                gap_weight: Some(5.0),
+                ..Default::default()
+            },
+        );
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let parameters = adapter.energy_parameters().unwrap();
+
+        let group = parameters.group(0);
+        assert_eq!(group.ideal_lessons_in_day, 2);
+        assert_eq!(group.gap_weight, 1.0);
+        let nowak = plan_input
+            .teachers
+            .iter()
+            .position(|x| x.name == "Nowak")
+            .unwrap();
+        let teacher = parameters.teacher(nowak as u8);
+        assert_eq!(teacher.ideal_lessons_in_day, 3);
+        assert_eq!(teacher.gap_weight, 5.0);
+    }
+
+    #[test]
+    fn unsatisfied_time_preferences_are_reported() {
+        let mut plan_input = plan_input();
+        plan_input
+            .soft_constraints
+            .time_preferences
+            .push(TimePreference {
+                subject: IllegalStateSubject::Teacher("Nowak".to_owned()),
+                kind: PreferenceKind::Prefer,
+                weight: 3.0,
+                session: None,
+                day: None,
+                weekday: None,
+                from: Some(ClockTime::new(18, 0)),
+                to: None,
+            });
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+        let mut statistics = BufferStatistics::new(adapter.energy_parameters().unwrap());
+        statistics.emplace_of_buffer(&buffer);
+
+        let report = adapter.energy_report(&statistics);
+        let preferences = report
+            .iter()
+            .find(|x| x.name == "time_preferences")
+            .unwrap();
+        // Pierwsze wolne miejsce to rano, a nie wieczór
+        assert_eq!(preferences.total, 1);
+        assert_eq!(preferences.energy, 3.0);
+        let entry = &preferences.entries[0];
+        assert_eq!(entry.teacher.as_deref(), Some("Nowak"));
+        assert_eq!(entry.time_preference, Some(0));
+    }
+
+    #[test]
+    fn lesson_limits_are_merged_with_general_limits() {
+        let mut plan_input = plan_input();
+        plan_input.hard_constraints.teacher_limits = LessonLimits {
+            min_lessons_in_day: Some(2),
+            max_lessons_in_day: Some(4),
+            max_lessons_in_session: None,
+        };
+        plan_input.hard_constraints.teachers.insert(
+            "Nowak".to_owned(),
+            LessonLimits {
+                max_lessons_in_session: Some(6),
+                ..Default::default()
+            },
+        );
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let parameters = adapter.energy_parameters().unwrap();
+        let limits = parameters.teacher_limits(1);
+        assert_eq!(limits.max_lessons_in_day, Some(4));
+        assert_eq!(limits.max_lessons_in_session, Some(6));
+        assert_eq!(parameters.group_limits(0), LessonLimits::default());
+
+        plan_input.hard_constraints.teachers.insert(
+            "Kowalski".to_owned(),
+            LessonLimits {
+                max_lessons_in_day: Some(1),
+                ..Default::default()
+            },
+        );
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        assert_eq!(
+            adapter.energy_parameters().err(),
+            Some(AdapterError::InvalidLessonLimits("Kowalski".to_owned()))
+        );
+    }
+
+    #[test]
+    fn invalid_soft_constraints_are_errors() {
+        let mut plan_input = plan_input();
+        plan_input
+            .soft_constraints
+            .teachers
+            .insert("Zieliński".to_owned(), DayOverride::default());
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        assert_eq!(
+            adapter.energy_parameters().err(),
+            Some(AdapterError::UnknownTeacher("Zieliński".to_owned()))
+        );
+
+        let mut plan_input = self::plan_input();
+        plan_input.soft_constraints.weights.teacher_gap_weight = -1.0;
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        assert!(matches!(
+            adapter.energy_parameters(),
+            Err(AdapterError::InvalidSoftConstraint(_))
+        ));
+    }
+
+    #[test]
+    fn pinned_lesson_is_placed_first_at_its_time() {
+        let mut plan_input = plan_input();
+        plan_input.pinned_lessons.push(PinnedLesson {
+            subject: "WF".to_owned(),
+            groups: vec!["11A".to_owned()],
+            time: Some(PinnedTime {
+                session: 0,
+                day: 0,
+                start: ClockTime::new(8, 0),
+            }),
+            teacher: None,
+            classroom: None,
+        });
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+        let output = adapter.buffer_to_output(&buffer);
+
+        let wf = output
+            .lessons
+            .iter()
+            .find(|x| x.subject_name == "WF")
+            .unwrap();
+        assert_eq!(wf.start, ClockTime::new(8, 0));
+        assert!(wf.pinned);
+        let math = output
+            .lessons
+            .iter()
+            .find(|x| x.subject_name == "matematyka")
+            .unwrap();
+        assert_ne!(math.start, ClockTime::new(8, 0));
+        assert!(!math.pinned);
+    }
+
+    #[test]
+    fn pinned_lesson_must_match_a_lesson_and_a_slot() {
+        let mut plan_input = plan_input();
+        plan_input.pinned_lessons.push(PinnedLesson {
+            subject: "WF".to_owned(),
+            groups: vec!["11A".to_owned()],
+            time: Some(PinnedTime {
+                session: 0,
+                day: 0,
+                start: ClockTime::new(9, 0),
+            }),
+            teacher: None,
+            classroom: None,
+        });
+        assert!(matches!(
+            AnnealingAdapter::of_plan_input(&plan_input).err(),
+            Some(AdapterError::InvalidPinnedLesson(_))
+        ));
+
+        // Grupa ma tylko jedną lekcję WF w zjeździe
+        plan_input.pinned_lessons[0].time = None;
+        plan_input
+            .pinned_lessons
+            .push(plan_input.pinned_lessons[0].clone());
+        assert!(matches!(
+            AnnealingAdapter::of_plan_input(&plan_input).err(),
+            Some(AdapterError::InvalidPinnedLesson(_))
+        ));
+    }
+
+    #[test]
+    fn warm_start_keeps_previous_placement() {
+        let plan_input = plan_input();
+        let lesson = |subject: &str, teacher: &str, hour| PreviousLesson {
+            groups: vec!["11A".to_owned()],
+            teacher: teacher.to_owned(),
+            classroom: "3.15d".to_owned(),
+            subject_name: subject.to_owned(),
+            session: 0,
+            day: 0,
+            start: ClockTime::new(hour, 0),
+        };
+        // Nauczyciela z poprzedniego planu już nie ma, a przedmiotu nie ma w danych
+        let previous_plan = PreviousPlan {
+            lessons: vec![
+                lesson("WF", "Nowak", 18),
+                lesson("matematyka", "Zieliński", 14),
+                lesson("fizyka", "Nowak", 8),
+            ],
+        };
+        let mut adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        assert_eq!(adapter.warm_start(&previous_plan), 2);
+
+        let illegal_buffer = adapter.create_illegal_buffer().unwrap();
+        let buffer = adapter.create_annealing_buffer(&illegal_buffer);
+        let output = adapter.buffer_to_output(&buffer);
+        let start = |subject| {
+            output
+                .lessons
+                .iter()
+                .find(|x| x.subject_name == subject)
+                .unwrap()
+                .start
+        };
+        assert_eq!(start("WF"), ClockTime::new(18, 0));
+        assert_eq!(start("matematyka"), ClockTime::new(14, 0));
+
+        let mut statistics = BufferStatistics::new(adapter.energy_parameters().unwrap());
+        statistics.emplace_of_buffer(&buffer);
+        let report = adapter.energy_report(&statistics);
+        let stability = report.iter().find(|x| x.name == "stability").unwrap();
+        assert_eq!(stability.total, 0);
+    }
+
+    #[test]
+    fn subject_without_qualified_teacher_is_an_error() {
+        let mut plan_input = plan_input();
+        plan_input.teachers.pop();
+        let adapter = AnnealingAdapter::of_plan_input(&plan_input).unwrap();
+        assert_eq!(
+            adapter.create_illegal_buffer().err(),
+            Some(AdapterError::NoQualifiedTeacher("WF".to_owned()))
+        );
+    }
 }
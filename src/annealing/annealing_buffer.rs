@@ -1,35 +1,55 @@
+use rand::Rng;
+use serde::Serialize;
+
+use crate::data_types::{AnnealingSettings, SlotTable};
+
 use super::{
     annealing_state::AnnealingState,
-    energy::{BufferStatistics, EnergyWeights},
+    energy::{BufferStatistics, EnergyParameters, Score},
     illegal_buffer::IllegalBuffer,
-    inner_state::{InnerCollision, InnerState},
+    inner_state::{ChangedLessons, InnerState},
     mutation::*,
 };
 
-use std::collections::{HashMap, HashSet};
-
-pub use super::inner_state::{ClassroomTimeKey, Lesson, TeacherTimeKey};
+pub use super::inner_state::{Lesson, LessonShape};
 
-#[derive(Hash, Eq, PartialEq)]
-pub struct CanTeach {
-    pub teacher_id: u8,
-    pub subject_id: u8,
+// Przebieg wyżarzania zapisywany w wyniku. Wynikiem jest najlepszy znaleziony
+// plan, który może być lepszy od planu z ostatniej iteracji.
+#[derive(Serialize, Debug, Clone, Copy, Default)]
+pub struct AnnealingRun {
+    pub initial_temperature: f32, // Podana w ustawieniach albo wyznaczona z próbnych mutacji
+    pub score: Score,
+    pub best_iteration: usize, // Numer przyjętej mutacji, po której plan był najlepszy
+    pub final_score: Score,
+    pub iterations: usize, // Liczba przyjętych mutacji
+    pub reheats: u32,
 }
 
-#[derive(Hash, PartialEq, Eq)]
-pub struct CanHold {
-    pub classroom_id: u8,
-    pub subject_id: u8,
+// Liczba mutacji próbkowanych przy wyznaczaniu temperatury początkowej
+const TEMPERATURE_SAMPLES: usize = 1000;
+// Temperatura początkowa, gdy żadna próbna mutacja nie pogorszyła energii
+const DEFAULT_TEMPERATURE: f32 = 10.0;
+// Prawdopodobieństwo, że zamiast mutacji wyżarzanie spróbuje umieścić w planie
+// lekcję spoza niego, jeśli takie są
+const INSERTION_PROBABILITY: f32 = 0.1;
+// Co tyle mutacji odrzuconych z rzędu sprawdzane są limity czasu
+const TIME_CHECK_INTERVAL: usize = 1000;
+
+// Zmiana planu wraz ze sposobem jej cofnięcia
+enum Change {
+    Mutation(ReverseMutation),
+    Insertion(ChangedLessons),
 }
 
 #[derive(Default)]
 pub struct AnnealingBuffer {
-    pub teacher_count: u8,
-    pub classroom_count: u8,
-    pub max_time: u8,
+    pub max_time: u16,
+    pub slots: SlotTable,
 
-    pub can_teach: HashSet<CanTeach>,
-    pub can_hold: HashSet<CanHold>,
+    // Nauczyciele, którzy mogą prowadzić daną lekcję, i sale, w których może się
+    // odbyć (indeksowane id lekcji)
+    pub lesson_teachers: Vec<Vec<u8>>,
+    pub lesson_classrooms: Vec<Vec<u8>>,
 
     pub inner_state: InnerState,
 }
@@ -43,102 +63,304 @@ impl std::fmt::Debug for AnnealingBuffer {
 }
 
 impl AnnealingBuffer {
-    pub fn new(number_of_lessons: usize, max_time: u8) -> Self {
+    pub fn new(lesson_shapes: Vec<LessonShape>, slots: SlotTable) -> Self {
         Self {
-            max_time,
-            inner_state: InnerState::new(number_of_lessons),
+            max_time: slots.len() as u16,
+            slots,
+            inner_state: InnerState::new(lesson_shapes),
             ..Default::default()
         }
     }
 
-    pub fn place_lesson(
-        &mut self,
-        lesson: usize,
-        teacher: u8,
-        classroom: u8,
-        time: u8,
-        group: u8,
-    ) -> bool {
+    pub fn place_lesson(&mut self, lesson: usize, teacher: u8, classroom: u8, time: u16) -> bool {
         self.inner_state
-            .place_lesson(lesson, teacher, classroom, time, group)
+            .place_lesson(lesson, teacher, classroom, time)
     }
 
+    // Zwraca None, jeśli mutacja nie została wykonana
     fn apply_mutation(
         &mut self,
         mutation: Mutation,
         illegal_buffer: &IllegalBuffer,
-    ) -> ReverseMutation {
+    ) -> Option<(ReverseMutation, ChangedLessons)> {
         let previous_lesson_state = self.inner_state.state_ref().lessons[mutation.target_lesson];
         let rev_mutation = mutation.reverse_mutation(previous_lesson_state);
         self.inner_state
-            .apply_mutation(mutation, Some(illegal_buffer));
-        rev_mutation
+            .apply_mutation(mutation, Some(illegal_buffer))
+            .map(|changed| (rev_mutation, changed))
     }
 
-    fn apply_reverse_mutation(&mut self, reverse_mutation: ReverseMutation) {
+    fn apply_reverse_mutation(&mut self, reverse_mutation: ReverseMutation) -> ChangedLessons {
+        self.inner_state
+            .apply_mutation(reverse_mutation.get(), None)
+            .expect("Reverse mutation must always succeed")
+    }
+
+    // Próbuje umieścić losową lekcję spoza planu w losowym stanie, w razie
+    // potrzeby usuwając z planu lekcję, z którą koliduje
+    fn insert_unplaced(
+        &mut self,
+        illegal_buffer: &IllegalBuffer,
+        rng: &mut impl Rng,
+    ) -> Option<ChangedLessons> {
+        let state_ref = self.inner_state.state_ref();
+        let unplaced = (0..state_ref.lessons.len())
+            .filter(|&x| !state_ref.placed[x])
+            .collect::<Vec<_>>();
+        let lesson_id = unplaced[rng.gen::<usize>() % unplaced.len()];
+        let teachers = &self.lesson_teachers[lesson_id];
+        let classrooms = &self.lesson_classrooms[lesson_id];
+        if teachers.is_empty() || classrooms.is_empty() {
+            return None;
+        }
+        let lesson = Lesson {
+            time: if state_ref.pinned_time[lesson_id] {
+                state_ref.lessons[lesson_id].time
+            } else {
+                rng.gen::<u16>() % self.max_time
+            },
+            teacher: teachers[rng.gen::<usize>() % teachers.len()],
+            classroom: classrooms[rng.gen::<usize>() % classrooms.len()],
+        };
         self.inner_state
-            .apply_mutation(reverse_mutation.get(), None);
+            .insert_lesson(lesson_id, lesson, illegal_buffer)
+    }
+
+    // Losowa mutacja albo, jeśli są lekcje spoza planu, czasem próba umieszczenia
+    // jednej z nich. Zwraca None, jeśli plan się nie zmienił.
+    fn apply_change(
+        &mut self,
+        statistics: &BufferStatistics,
+        illegal_buffer: &IllegalBuffer,
+        rng: &mut impl Rng,
+    ) -> Option<(Change, ChangedLessons)> {
+        let unplaced = statistics.unplaced_lessons() as usize;
+        let all_unplaced = unplaced == self.inner_state.state_ref().lessons.len();
+        if unplaced > 0 && (all_unplaced || rng.gen::<f32>() < INSERTION_PROBABILITY) {
+            return self
+                .insert_unplaced(illegal_buffer, rng)
+                .map(|changed| (Change::Insertion(changed), changed));
+        }
+        let mutation = Mutation::legal_of_buffer(self, illegal_buffer, rng);
+        self.apply_mutation(mutation, illegal_buffer)
+            .map(|(rev_mutation, changed)| (Change::Mutation(rev_mutation), changed))
+    }
+
+    fn revert_change(&mut self, change: Change) -> ChangedLessons {
+        match change {
+            Change::Mutation(rev_mutation) => self.apply_reverse_mutation(rev_mutation),
+            Change::Insertion(changed) => self.inner_state.revert_insertion(changed),
+        }
+    }
+
+    // Temperatura, przy której pogorszenie energii o średnią z losowych mutacji
+    // jest przyjmowane z prawdopodobieństwem `acceptance`. Plan pozostaje bez zmian.
+    fn initial_temperature(
+        &mut self,
+        parameters: &EnergyParameters,
+        illegal_buffer: &IllegalBuffer,
+        acceptance: f32,
+        rng: &mut impl Rng,
+    ) -> f32 {
+        let mut statistics = BufferStatistics::new(parameters.clone());
+        statistics.emplace_of_buffer(self);
+        if !self.inner_state.state_ref().placed.contains(&true) {
+            return DEFAULT_TEMPERATURE;
+        }
+
+        let mut uphill = Vec::new();
+        for _ in 0..TEMPERATURE_SAMPLES {
+            let mutation = Mutation::legal_of_buffer(self, illegal_buffer, rng);
+            let (rev_mutation, changed) = match self.apply_mutation(mutation, illegal_buffer) {
+                Some(x) => x,
+                None => continue,
+            };
+            let hard_violations = statistics.hard_violations();
+            let delta = statistics.update(self, changed);
+            // Zmiany liczby naruszeń nie zależą od temperatury
+            if delta > 0.0 && statistics.hard_violations() == hard_violations {
+                uphill.push(delta);
+            }
+            let changed = self.apply_reverse_mutation(rev_mutation);
+            statistics.update(self, changed);
+        }
+
+        if uphill.is_empty() {
+            return DEFAULT_TEMPERATURE;
+        }
+        let mean = uphill.iter().sum::<f32>() / uphill.len() as f32;
+        -mean / acceptance.ln()
     }
 
     pub fn anneal_iterations(
         &mut self,
-        iterations: usize,
-        weights: &EnergyWeights,
+        settings: &AnnealingSettings,
+        parameters: &EnergyParameters,
         illegal_buffer: IllegalBuffer,
-    ) {
-        let mut annealing_state = AnnealingState::new(iterations);
-        let mut statistics = BufferStatistics::new();
+        rng: &mut impl Rng,
+    ) -> AnnealingRun {
+        let initial_temperature = settings.initial_temperature.unwrap_or_else(|| {
+            self.initial_temperature(
+                parameters,
+                &illegal_buffer,
+                settings.initial_acceptance,
+                rng,
+            )
+        });
+        let mut annealing_state = AnnealingState::new(settings, initial_temperature);
+        let mut statistics = BufferStatistics::new(parameters.clone());
         statistics.emplace_of_buffer(self);
 
+        let mut run = AnnealingRun {
+            initial_temperature,
+            score: statistics.score(),
+            final_score: statistics.score(),
+            ..Default::default()
+        };
+
+        if self.inner_state.state_ref().lessons.is_empty() {
+            return run;
+        }
+
         let mut rejected = 0_f64;
         let mut max_rejected = 0_usize;
 
-        let mut prev_energy = 0.0f32;
-        let mut const_energy_count = 0usize;
+        let mut prev_score = statistics.score();
+        let mut const_score_count = 0usize;
+
+        // Najlepszy dotąd plan, do którego wyżarzanie wraca po podgrzaniu i który
+        // jest wynikiem wyżarzania
+        let mut best_score = statistics.score();
+        let mut best_lessons = self.inner_state.state_ref().lessons.clone();
+        let mut best_placed = self.inner_state.state_ref().placed.clone();
+
+        let mut best_time = 0.0;
+
+        // Powód przerwania wyżarzania po przekroczeniu limitu czasu
+        let out_of_time = |annealing_state: &AnnealingState, best_time: f32| {
+            if annealing_state.is_out_of_time() {
+                return Some("Przekroczono limit czasu".to_owned());
+            }
+            match settings.unimproved_time_limit {
+                Some(limit) if annealing_state.elapsed() - best_time >= limit => {
+                    Some(format!("Brak poprawy od {} s", limit))
+                }
+                _ => None,
+            }
+        };
+
+        let limit = settings.stagnation_limit;
+        'annealing: for i in 0..settings.iterations {
+            if let Some(reason) = out_of_time(&annealing_state, best_time) {
+                println!("\n{}, przerywam", reason);
+                break;
+            }
 
-        for i in 0..iterations {
-            let last_energy = statistics.energy(weights);
+            let last_score = statistics.score();
+            let last_energy = last_score.soft;
 
-            if prev_energy == last_energy {
-                const_energy_count += 1;
-                if const_energy_count == 1_000_000 {
-                    println!("1,000,000 mutacji bez zmian energii, przerywam");
-                    return;
+            // Zmiana liczby naruszeń też przerywa zastój, nawet przy tej samej energii
+            let mut stagnation = None;
+            if prev_score == last_score {
+                const_score_count += 1;
+                if const_score_count == limit {
+                    stagnation = Some(format!("{} mutacji bez zmian naruszeń i energii", limit));
                 }
             } else {
-                prev_energy = last_energy;
-                const_energy_count = 0;
+                prev_score = last_score;
+                const_score_count = 0;
             }
 
-            for j in 1..=1_000_000 {
-                let mutation = Mutation::legal_of_buffer(self);
-                let rev_mutation = self.apply_mutation(mutation, &illegal_buffer);
-                statistics.emplace_of_buffer(self);
-                let new_energy = statistics.energy(weights);
-                if !annealing_state.should_accept_state(last_energy, new_energy) {
-                    self.apply_reverse_mutation(rev_mutation);
-                    rejected += 1.0;
-                    max_rejected = usize::max(max_rejected, j);
-                } else {
+            let mut accepted = false;
+            if stagnation.is_none() {
+                for j in 1..=limit {
+                    if j % TIME_CHECK_INTERVAL == 0 {
+                        if let Some(reason) = out_of_time(&annealing_state, best_time) {
+                            println!("\n{}, przerywam", reason);
+                            break 'annealing;
+                        }
+                        annealing_state.update_temperature();
+                    }
+                    let (change, changed) =
+                        match self.apply_change(&statistics, &illegal_buffer, rng) {
+                            Some(x) => x,
+                            None => {
+                                rejected += 1.0;
+                                continue;
+                            }
+                        };
+                    let delta = statistics.update(self, changed);
+                    let new_score = Score {
+                        hard: statistics.hard_violations(),
+                        soft: last_energy + delta,
+                    };
+                    if !annealing_state.should_accept_state(last_score, new_score, rng) {
+                        let changed = self.revert_change(change);
+                        statistics.update(self, changed);
+                        rejected += 1.0;
+                        max_rejected = usize::max(max_rejected, j);
+                    } else {
+                        accepted = true;
+                        break;
+                    }
+                }
+            }
+
+            if !accepted {
+                let reason = stagnation.unwrap_or_else(|| format!("{} odrzuconych mutacji", limit));
+                if run.reheats == settings.max_reheats {
+                    println!("\n{}, przerywam", reason);
                     break;
                 }
-                if j == 1_000_000 {
-                    println!("\n1,000,000 odrzuconych mutacji, przerywam");
-                    return;
+                run.reheats += 1;
+                if settings.restart_from_best {
+                    self.inner_state
+                        .restore_lessons(&best_lessons, &best_placed);
+                    statistics.emplace_of_buffer(self);
                 }
+                annealing_state.reheat(initial_temperature * settings.reheat_fraction);
+                println!(
+                    "\n{}, podgrzewam ({}) do temperatury {}",
+                    reason,
+                    run.reheats,
+                    annealing_state.temperature()
+                );
+                const_score_count = 0;
+                continue;
+            }
+
+            run.iterations += 1;
+            if statistics.score() < best_score {
+                best_score = statistics.score();
+                best_lessons.copy_from_slice(self.inner_state.state_ref().lessons);
+                best_placed.copy_from_slice(self.inner_state.state_ref().placed);
+                run.best_iteration = run.iterations;
+                best_time = annealing_state.elapsed();
             }
             print!(
-                "\rPrzyjęto {}, energia = {} (brak zmian od {}), maks odrzuconych z rzędu: {}, % odrzuconych: {}, temp: {},    ",
+                "\rPrzyjęto {}, naruszenia = {}, energia = {} (brak zmian od {}), maks odrzuconych z rzędu: {}, % odrzuconych: {}, temp: {},    ",
                 i + 1,
-                statistics.energy(weights),
-                const_energy_count,
+                statistics.hard_violations(),
+                statistics.energy(),
+                const_score_count,
                 max_rejected,
                 (rejected / i as f64) * 100.0,
                 annealing_state.temperature(),
             );
-            self.assert_maps_synchronized("After mutation accepted");
+            if cfg!(debug_assertions) {
+                self.assert_maps_synchronized("After mutation accepted");
+            }
             annealing_state.do_step();
         }
+
+        run.final_score = statistics.score();
+        if best_score < run.final_score {
+            self.inner_state
+                .restore_lessons(&best_lessons, &best_placed);
+            statistics.emplace_of_buffer(self);
+        }
+        run.score = statistics.score();
+        run
     }
 
     pub fn assert_maps_synchronized(&self, msg: &str) {
@@ -149,11 +371,174 @@ impl AnnealingBuffer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
     #[test]
     fn placing_lesson_works() {
-        let mut annealing_buffer = AnnealingBuffer::new(3, 10);
-        assert!(annealing_buffer.place_lesson(0, 0, 0, 0, 0));
-        assert!(annealing_buffer.place_lesson(1, 0, 0, 1, 0));
-        assert_eq!(false, annealing_buffer.place_lesson(2, 0, 0, 0, 0));
+        let slots = SlotTable::new(&Default::default(), 2, 1).unwrap();
+        let mut annealing_buffer =
+            AnnealingBuffer::new(vec![LessonShape::new(vec![0], 1, 0); 3], slots);
+        assert!(annealing_buffer.place_lesson(0, 0, 0, 0));
+        assert!(annealing_buffer.place_lesson(1, 0, 0, 1));
+        assert!(!annealing_buffer.place_lesson(2, 0, 0, 0));
+    }
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(0)
+    }
+
+    // Lekcje jednej grupy z jednym nauczycielem i salą, w jednym dniu
+    fn buffer_of_lessons(count: usize) -> (AnnealingBuffer, IllegalBuffer) {
+        let slots = SlotTable::new(&Default::default(), 1, 1).unwrap();
+        let shapes = vec![LessonShape::new(vec![0], 1, 0); count];
+        let illegal_buffer = IllegalBuffer::permissive(&shapes, &slots, 1, 1);
+        let mut buffer = AnnealingBuffer::new(shapes, slots);
+        buffer.lesson_teachers = vec![vec![0]; count];
+        buffer.lesson_classrooms = vec![vec![0]; count];
+        for lesson_id in 0..count {
+            assert!(buffer.place_lesson(lesson_id, 0, 0, lesson_id as u16));
+        }
+        (buffer, illegal_buffer)
+    }
+
+    #[test]
+    fn stagnating_annealing_is_reheated() {
+        let (mut buffer, illegal_buffer) = buffer_of_lessons(1);
+
+        // Jedyna lekcja w dniu ma tę samą energię w każdym terminie
+        let settings = AnnealingSettings {
+            iterations: 1000,
+            initial_temperature: Some(1.0),
+            stagnation_limit: 10,
+            max_reheats: 2,
+            ..Default::default()
+        };
+        let run =
+            buffer.anneal_iterations(&settings, &Default::default(), illegal_buffer, &mut rng());
+        assert_eq!(run.reheats, 2);
+        assert!(run.iterations < 100);
+        buffer.assert_maps_synchronized("After reheating");
+    }
+
+    #[test]
+    fn annealing_stops_at_time_limit() {
+        let (mut buffer, illegal_buffer) = buffer_of_lessons(2);
+        let settings = AnnealingSettings {
+            iterations: usize::MAX,
+            time_limit: Some(0.1),
+            initial_temperature: Some(1.0),
+            stagnation_limit: usize::MAX,
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let run =
+            buffer.anneal_iterations(&settings, &Default::default(), illegal_buffer, &mut rng());
+        assert!(start.elapsed().as_secs_f32() < 5.0);
+        assert!(run.iterations > 0);
+    }
+
+    #[test]
+    fn time_limit_stops_rejected_mutations() {
+        let (mut buffer, _) = buffer_of_lessons(1);
+        buffer.inner_state.unplace_lesson(0);
+        // Lekcji nie może prowadzić żaden nauczyciel, więc każda próba jej
+        // umieszczenia jest odrzucana
+        let illegal_buffer =
+            IllegalBuffer::permissive(&[LessonShape::new(vec![0], 1, 0)], &buffer.slots, 0, 1);
+        let settings = AnnealingSettings {
+            iterations: usize::MAX,
+            time_limit: Some(0.1),
+            initial_temperature: Some(1.0),
+            stagnation_limit: usize::MAX,
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let run =
+            buffer.anneal_iterations(&settings, &Default::default(), illegal_buffer, &mut rng());
+        assert!(start.elapsed().as_secs_f32() < 5.0);
+        assert_eq!(run.iterations, 0);
+    }
+
+    #[test]
+    fn same_seed_gives_same_plan() {
+        let settings = AnnealingSettings {
+            iterations: 500,
+            initial_temperature: Some(1000.0),
+            final_temperature: 1000.0,
+            ..Default::default()
+        };
+        let plans = (0..2)
+            .map(|_| {
+                let (mut buffer, illegal_buffer) = buffer_of_lessons(3);
+                let run = buffer.anneal_iterations(
+                    &settings,
+                    &Default::default(),
+                    illegal_buffer,
+                    &mut StdRng::seed_from_u64(7),
+                );
+                (
+                    run.final_score,
+                    buffer.inner_state.state_ref().lessons.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(plans[0], plans[1]);
+    }
+
+    #[test]
+    fn best_plan_is_returned() {
+        let (mut buffer, illegal_buffer) = buffer_of_lessons(2);
+
+        // Przy stałej wysokiej temperaturze wyżarzanie błądzi losowo, a najlepszy
+        // jest plan początkowy z lekcjami bez okienka
+        let settings = AnnealingSettings {
+            iterations: 200,
+            initial_temperature: Some(1000.0),
+            final_temperature: 1000.0,
+            max_reheats: 0,
+            ..Default::default()
+        };
+        let parameters = EnergyParameters::default();
+        let run = buffer.anneal_iterations(&settings, &parameters, illegal_buffer, &mut rng());
+        assert!(run.score <= run.final_score);
+
+        let mut statistics = BufferStatistics::new(parameters);
+        statistics.emplace_of_buffer(&buffer);
+        assert_eq!(statistics.score(), run.score);
+        let lessons = buffer.inner_state.state_ref().lessons;
+        assert_eq!((lessons[0].time as i32 - lessons[1].time as i32).abs(), 1);
+    }
+
+    #[test]
+    fn lesson_left_out_by_construction_is_placed() {
+        use crate::annealing::construction::{construct, Candidates, LessonOrigin};
+        let (mut buffer, illegal_buffer) = buffer_of_lessons(2);
+        buffer.inner_state.unplace_lesson(0);
+        buffer.inner_state.unplace_lesson(1);
+
+        // Nowa lekcja może zająć tylko termin lekcji z poprzedniego planu
+        let candidates = [LessonOrigin::Previous, LessonOrigin::New]
+            .iter()
+            .map(|&origin| Candidates {
+                times: vec![0],
+                teachers: vec![0],
+                classrooms: vec![0],
+                origin,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            construct(&mut buffer, &illegal_buffer, &candidates),
+            vec![1]
+        );
+
+        let settings = AnnealingSettings {
+            iterations: 100,
+            initial_temperature: Some(1.0),
+            ..Default::default()
+        };
+        let run =
+            buffer.anneal_iterations(&settings, &Default::default(), illegal_buffer, &mut rng());
+        assert_eq!(run.score.hard, 0);
+        assert_eq!(buffer.inner_state.state_ref().placed, &[true, true]);
+        buffer.assert_maps_synchronized("After placing unplaced lesson");
     }
 }
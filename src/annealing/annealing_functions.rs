@@ -1,18 +1,19 @@
-use rand::random;
+use rand::Rng;
 
 pub fn probability(last_energy: f32, new_energy: f32, temperature: f32) -> f32 {
     f32::exp(-(new_energy - last_energy) / temperature)
 }
 
-pub fn temperature(time: f32) -> f32 {
-    f32::max(0.2, 1.0 - time * 0.5)
-}
-
-pub fn should_accept_state(last_energy: f32, new_energy: f32, temperature: f32) -> bool {
+pub fn should_accept_state(
+    last_energy: f32,
+    new_energy: f32,
+    temperature: f32,
+    rng: &mut impl Rng,
+) -> bool {
     if new_energy < last_energy {
         true
     } else {
-        let r = random::<f32>();
+        let r = rng.gen::<f32>();
         let probability = probability(last_energy, new_energy, temperature);
         probability >= r
     }
@@ -1,31 +1,131 @@
-#[derive(Debug, Default)]
+use std::time::Instant;
+
+use rand::Rng;
+
+use crate::data_types::AnnealingSettings;
+
+use super::{
+    cooling::{self, CoolingSchedule},
+    energy::Score,
+};
+
+#[derive(Debug)]
 pub struct AnnealingState {
     iteration: usize,
     max_iterations: usize,
     temperature: f32,
+    settings: AnnealingSettings,
+    schedule: Box<dyn CoolingSchedule>,
+    // Iteracja i czas w sekundach, od których liczony jest postęp chłodzenia
+    // (ostatnie podgrzanie)
+    schedule_start: usize,
+    schedule_start_time: f32,
+    start: Instant,
 }
 
 impl AnnealingState {
-    pub fn new(max_iterations: usize) -> Self {
+    pub fn new(settings: &AnnealingSettings, initial_temperature: f32) -> Self {
+        let mut schedule = cooling::schedule(settings, initial_temperature);
         Self {
             iteration: 0,
-            max_iterations,
-            temperature: 10.0,
+            max_iterations: settings.iterations,
+            temperature: schedule.temperature(0.0),
+            settings: settings.clone(),
+            schedule,
+            schedule_start: 0,
+            schedule_start_time: 0.0,
+            start: Instant::now(),
         }
     }
 
-    pub fn should_accept_state(&mut self, last_energy: f32, new_energy: f32) -> bool {
+    // Oceny są porównywane leksykograficznie: zmiana łamiąca więcej twardych
+    // ograniczeń jest zawsze odrzucana, a naprawiająca je zawsze przyjmowana.
+    // Przy tej samej liczbie naruszeń decyduje energia i temperatura.
+    pub fn should_accept_state(
+        &mut self,
+        last_score: Score,
+        new_score: Score,
+        rng: &mut impl Rng,
+    ) -> bool {
         use super::annealing_functions::should_accept_state as should_accept;
-        should_accept(last_energy, new_energy, self.temperature)
+        match new_score.hard.cmp(&last_score.hard) {
+            std::cmp::Ordering::Less => true,
+            std::cmp::Ordering::Greater => false,
+            std::cmp::Ordering::Equal => {
+                let accepted =
+                    should_accept(last_score.soft, new_score.soft, self.temperature, rng);
+                if new_score.soft > last_score.soft {
+                    self.schedule.record_uphill(accepted);
+                }
+                accepted
+            }
+        }
     }
 
     pub fn do_step(&mut self) {
-        use super::annealing_functions::temperature;
         self.iteration += 1;
-        self.temperature *= 0.999999;
+        self.update_temperature();
+    }
+
+    // Postęp chłodzenia według liczby iteracji albo czasu, jeśli ten jest bliżej
+    // limitu. Wywoływane także w trakcie długich serii odrzuconych mutacji, żeby
+    // temperatura nadążała za upływającym czasem.
+    pub fn update_temperature(&mut self) {
+        let remaining = self
+            .max_iterations
+            .saturating_sub(self.schedule_start)
+            .max(1);
+        let mut progress = (self.iteration - self.schedule_start) as f32 / remaining as f32;
+        if let Some(time_limit) = self.settings.time_limit {
+            let remaining_time = time_limit - self.schedule_start_time;
+            let time_progress = (self.elapsed() - self.schedule_start_time) / remaining_time;
+            progress = progress.max(time_progress);
+        }
+        self.temperature = self.schedule.temperature(progress.min(1.0));
+    }
+
+    // Podnosi temperaturę i chłodzi od nowa przez pozostałe iteracje i czas
+    pub fn reheat(&mut self, temperature: f32) {
+        self.schedule = cooling::schedule(&self.settings, temperature);
+        self.schedule_start = self.iteration;
+        self.schedule_start_time = self.elapsed();
+        self.temperature = self.schedule.temperature(0.0);
+    }
+
+    // Czas od rozpoczęcia wyżarzania w sekundach
+    pub fn elapsed(&self) -> f32 {
+        self.start.elapsed().as_secs_f32()
+    }
+
+    pub fn is_out_of_time(&self) -> bool {
+        self.settings
+            .time_limit
+            .is_some_and(|time_limit| self.elapsed() >= time_limit)
     }
 
     pub fn temperature(&self) -> f32 {
         self.temperature
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_follows_time_without_steps() {
+        let settings = AnnealingSettings {
+            iterations: usize::MAX,
+            time_limit: Some(0.05),
+            ..Default::default()
+        };
+        let mut annealing_state = AnnealingState::new(&settings, 100.0);
+        let initial = annealing_state.temperature();
+
+        // Żadna mutacja nie została przyjęta, ale limit czasu już minął
+        std::thread::sleep(std::time::Duration::from_millis(60));
+        annealing_state.update_temperature();
+        assert!(annealing_state.temperature() < initial);
+        assert_eq!(annealing_state.iteration, 0);
+    }
+}
@@ -0,0 +1,302 @@
+use itertools::iproduct;
+
+use super::{
+    annealing_buffer::{AnnealingBuffer, Lesson},
+    illegal_buffer::IllegalBuffer,
+    inner_state::InnerCollision,
+};
+
+// Ile stanów zajętych przez jedną inną lekcję jest łącznie sprawdzanych dla lekcji,
+// dla której zabrakło wolnego miejsca
+const MAX_EJECTIONS: usize = 1000;
+// Ile lekcji może zostać kolejno przeniesionych, aby zrobić miejsce dla jednej
+const MAX_EJECTION_DEPTH: usize = 3;
+
+// Kolejność umieszczania lekcji w planie początkowym
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LessonOrigin {
+    Pinned,
+    Previous, // Lekcja z poprzedniego planu
+    New,
+}
+
+// Możliwe stany jednej lekcji w kolejności, w jakiej są sprawdzane
+#[derive(Debug, Clone)]
+pub struct Candidates {
+    pub times: Vec<u16>,
+    pub teachers: Vec<u8>,
+    pub classrooms: Vec<u8>,
+    pub origin: LessonOrigin,
+}
+
+impl Candidates {
+    fn lessons(&self) -> impl Iterator<Item = Lesson> + '_ {
+        iproduct!(
+            self.times.iter(),
+            self.teachers.iter(),
+            self.classrooms.iter()
+        )
+        .map(|(&time, &teacher, &classroom)| Lesson {
+            time,
+            teacher,
+            classroom,
+        })
+    }
+}
+
+// Buduje plan początkowy. Najpierw są umieszczane lekcje przypięte i z poprzedniego
+// planu, potem pozostałe, zaczynając od tych, które mają najmniej dozwolonych stanów.
+// Jeśli dla lekcji zabrakło miejsca, może zająć miejsce innej nowej lekcji, o ile
+// tamtą da się przenieść gdzie indziej, choćby na miejsce kolejnej. Zwraca lekcje,
+// których nie udało się umieścić.
+pub fn construct(
+    buffer: &mut AnnealingBuffer,
+    illegal_buffer: &IllegalBuffer,
+    candidates: &[Candidates],
+) -> Vec<usize> {
+    let legal_states = |lesson_id: usize| {
+        let candidates = &candidates[lesson_id];
+        illegal_buffer.count_legal_states(
+            lesson_id,
+            &candidates.times,
+            &candidates.teachers,
+            &candidates.classrooms,
+        )
+    };
+    let mut order = (0..candidates.len())
+        .map(|lesson_id| {
+            let origin = candidates[lesson_id].origin;
+            let legal_states = match origin {
+                LessonOrigin::New => legal_states(lesson_id),
+                _ => 0,
+            };
+            (origin, legal_states, lesson_id)
+        })
+        .collect::<Vec<_>>();
+    order.sort_unstable();
+
+    let mut unplaced = Vec::new();
+    for (origin, _, lesson_id) in order {
+        let placed = place(buffer, illegal_buffer, lesson_id, &candidates[lesson_id])
+            || (origin == LessonOrigin::New
+                && place_with_ejection(buffer, illegal_buffer, lesson_id, candidates));
+        if !placed {
+            unplaced.push(lesson_id);
+        }
+    }
+    unplaced
+}
+
+fn place(
+    buffer: &mut AnnealingBuffer,
+    illegal_buffer: &IllegalBuffer,
+    lesson_id: usize,
+    candidates: &Candidates,
+) -> bool {
+    let placement = candidates.lessons().find(|&lesson| {
+        !illegal_buffer.is_illegal(lesson_id, lesson)
+            && buffer.inner_state.check_collision(lesson, lesson_id) == InnerCollision::NoCollisions
+    });
+    match placement {
+        Some(lesson) => {
+            buffer.place_lesson(lesson_id, lesson.teacher, lesson.classroom, lesson.time)
+        }
+        None => false,
+    }
+}
+
+fn place_with_ejection(
+    buffer: &mut AnnealingBuffer,
+    illegal_buffer: &IllegalBuffer,
+    lesson_id: usize,
+    candidates: &[Candidates],
+) -> bool {
+    let mut budget = MAX_EJECTIONS;
+    eject_chain(
+        buffer,
+        illegal_buffer,
+        candidates,
+        &mut vec![lesson_id],
+        &mut budget,
+    )
+}
+
+// Umieszcza ostatnią lekcję łańcucha w miejscu innej nowej lekcji spoza łańcucha
+// i przenosi tamtą w wolne miejsce albo, jeśli łańcuch jest krótszy niż
+// MAX_EJECTION_DEPTH, w miejsce kolejnej lekcji. Jeśli się nie uda, plan pozostaje
+// bez zmian.
+fn eject_chain(
+    buffer: &mut AnnealingBuffer,
+    illegal_buffer: &IllegalBuffer,
+    candidates: &[Candidates],
+    chain: &mut Vec<usize>,
+    budget: &mut usize,
+) -> bool {
+    let lesson_id = chain[chain.len() - 1];
+    let ejections = candidates[lesson_id]
+        .lessons()
+        .filter(|&lesson| !illegal_buffer.is_illegal(lesson_id, lesson))
+        .filter_map(
+            |lesson| match buffer.inner_state.check_collision(lesson, lesson_id) {
+                InnerCollision::CollidesWithOne(other)
+                    if candidates[other].origin == LessonOrigin::New && !chain.contains(&other) =>
+                {
+                    Some((lesson, other))
+                }
+                _ => None,
+            },
+        )
+        .take(*budget)
+        .collect::<Vec<_>>();
+
+    for (lesson, other) in ejections {
+        // Limit jest wspólny dla całego łańcucha
+        if *budget == 0 {
+            break;
+        }
+        *budget -= 1;
+
+        let other_lesson = buffer.inner_state.unplace_lesson(other);
+        if buffer.place_lesson(lesson_id, lesson.teacher, lesson.classroom, lesson.time) {
+            chain.push(other);
+            let placed = place(buffer, illegal_buffer, other, &candidates[other])
+                || (chain.len() <= MAX_EJECTION_DEPTH
+                    && eject_chain(buffer, illegal_buffer, candidates, chain, budget));
+            chain.pop();
+            if placed {
+                return true;
+            }
+            buffer.inner_state.unplace_lesson(lesson_id);
+        }
+        let restored = buffer.place_lesson(
+            other,
+            other_lesson.teacher,
+            other_lesson.classroom,
+            other_lesson.time,
+        );
+        debug_assert!(restored, "Ejected lesson {} cannot be restored", other);
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{annealing::annealing_buffer::LessonShape, data_types::SlotTable};
+
+    #[test]
+    fn most_constrained_lesson_is_placed_first() {
+        let slots = SlotTable::new(&Default::default(), 1, 1).unwrap();
+        let shapes = vec![
+            LessonShape::new(vec![0], 1, 0),
+            LessonShape::new(vec![1], 1, 1),
+        ];
+        let illegal_buffer = IllegalBuffer::permissive(&shapes, &slots, 2, 1);
+        let mut buffer = AnnealingBuffer::new(shapes, slots);
+
+        // Lekcja 1 może się odbyć tylko o 8:00, lekcja 0 w dowolnym terminie
+        let candidates = vec![
+            Candidates {
+                times: (0..6).collect(),
+                teachers: vec![0],
+                classrooms: vec![0],
+                origin: LessonOrigin::New,
+            },
+            Candidates {
+                times: vec![0],
+                teachers: vec![0],
+                classrooms: vec![0],
+                origin: LessonOrigin::New,
+            },
+        ];
+        assert!(construct(&mut buffer, &illegal_buffer, &candidates).is_empty());
+        let lessons = buffer.inner_state.state_ref().lessons;
+        assert_eq!(lessons[1].time, 0);
+        assert_eq!(lessons[0].time, 1);
+    }
+
+    #[test]
+    fn lesson_without_free_place_moves_another_one() {
+        let slots = SlotTable::new(&Default::default(), 1, 1).unwrap();
+        let shapes = vec![LessonShape::new(vec![0], 1, 0); 3];
+        let illegal_buffer = IllegalBuffer::permissive(&shapes, &slots, 2, 1);
+        let mut buffer = AnnealingBuffer::new(shapes, slots);
+
+        // Lekcja 2 pasuje tylko tam, gdzie najpierw trafia lekcja 1, a lekcja 0
+        // jest przypięta do jedynego innego terminu
+        let candidates = vec![
+            Candidates {
+                times: vec![1],
+                teachers: vec![0],
+                classrooms: vec![0],
+                origin: LessonOrigin::Pinned,
+            },
+            Candidates {
+                times: vec![0, 2],
+                teachers: vec![0],
+                classrooms: vec![0],
+                origin: LessonOrigin::New,
+            },
+            Candidates {
+                times: vec![0, 1],
+                teachers: vec![0],
+                classrooms: vec![0],
+                origin: LessonOrigin::New,
+            },
+        ];
+        assert!(construct(&mut buffer, &illegal_buffer, &candidates).is_empty());
+        let lessons = buffer.inner_state.state_ref().lessons;
+        assert_eq!(
+            lessons.iter().map(|x| x.time).collect::<Vec<_>>(),
+            vec![1, 2, 0]
+        );
+
+        // Trzecia lekcja w jednym z dwóch terminów się nie zmieści
+        let mut buffer = AnnealingBuffer::new(
+            vec![LessonShape::new(vec![0], 1, 0); 3],
+            SlotTable::new(&Default::default(), 1, 1).unwrap(),
+        );
+        let two_times = Candidates {
+            times: vec![0, 1],
+            teachers: vec![0, 1],
+            classrooms: vec![0],
+            origin: LessonOrigin::New,
+        };
+        let unplaced = construct(&mut buffer, &illegal_buffer, &vec![two_times; 3]);
+        assert_eq!(unplaced.len(), 1);
+        assert!(!buffer.inner_state.state_ref().placed[unplaced[0]]);
+        buffer.assert_maps_synchronized("After construction");
+    }
+
+    #[test]
+    fn lesson_can_move_a_chain_of_lessons() {
+        let slots = SlotTable::new(&Default::default(), 1, 1).unwrap();
+        let shapes = vec![LessonShape::new(vec![0], 1, 0); 4];
+        let illegal_buffer = IllegalBuffer::permissive(&shapes, &slots, 1, 1);
+        let mut buffer = AnnealingBuffer::new(shapes, slots);
+
+        // Lekcje 0 i 1 zajmują pierwsze terminy, a drugi termin lekcji 2 należy do
+        // przypiętej lekcji 3. Lekcja 0 musi przejść na miejsce lekcji 1, a ta
+        // na jedyne wolne.
+        let candidates = [vec![0, 1], vec![1, 2], vec![0, 3], vec![3]]
+            .iter()
+            .enumerate()
+            .map(|(lesson_id, times)| Candidates {
+                times: times.clone(),
+                teachers: vec![0],
+                classrooms: vec![0],
+                origin: match lesson_id {
+                    3 => LessonOrigin::Pinned,
+                    _ => LessonOrigin::New,
+                },
+            })
+            .collect::<Vec<_>>();
+        assert!(construct(&mut buffer, &illegal_buffer, &candidates).is_empty());
+        let lessons = buffer.inner_state.state_ref().lessons;
+        assert_eq!(
+            lessons.iter().map(|x| x.time).collect::<Vec<_>>(),
+            vec![1, 2, 0, 3]
+        );
+        buffer.assert_maps_synchronized("After construction");
+    }
+}
@@ -0,0 +1,188 @@
+use crate::data_types::{AnnealingSettings, CoolingKind};
+
+// Szybkość spadku temperatury w chłodzeniu logarytmicznym
+const LOG_SCALE: f32 = 1000.0;
+// Co tyle ocenionych pogorszeń chłodzenie adaptacyjne zmienia temperaturę
+const ADAPTIVE_WINDOW: u32 = 100;
+const ADAPTIVE_FACTOR: f32 = 1.05;
+
+// Temperatura w zależności od postępu wyżarzania. Wszystkie schematy poza
+// adaptacyjnym zaczynają od temperatury początkowej i kończą na końcowej.
+pub trait CoolingSchedule: std::fmt::Debug {
+    // Temperatura po wykonaniu części `progress` (od 0 do 1) wyżarzania
+    fn temperature(&mut self, progress: f32) -> f32;
+
+    // Wywoływane po ocenie każdej mutacji pogarszającej energię
+    fn record_uphill(&mut self, _accepted: bool) {}
+}
+
+#[derive(Debug)]
+struct Geometric {
+    initial: f32,
+    ratio: f32,
+}
+
+impl CoolingSchedule for Geometric {
+    fn temperature(&mut self, progress: f32) -> f32 {
+        self.initial * self.ratio.powf(progress)
+    }
+}
+
+#[derive(Debug)]
+struct Linear {
+    initial: f32,
+    last: f32,
+}
+
+impl CoolingSchedule for Linear {
+    fn temperature(&mut self, progress: f32) -> f32 {
+        self.initial + (self.last - self.initial) * progress
+    }
+}
+
+// T = T0 / (1 + c ln(1 + k))
+#[derive(Debug)]
+struct Logarithmic {
+    initial: f32,
+    c: f32,
+}
+
+impl CoolingSchedule for Logarithmic {
+    fn temperature(&mut self, progress: f32) -> f32 {
+        self.initial / (1.0 + self.c * (1.0 + LOG_SCALE * progress).ln())
+    }
+}
+
+// T_{k+1} = T_k / (1 + βT_k), czyli 1/T rośnie liniowo
+#[derive(Debug)]
+struct LundyMees {
+    initial: f32,
+    last: f32,
+}
+
+impl CoolingSchedule for LundyMees {
+    fn temperature(&mut self, progress: f32) -> f32 {
+        1.0 / (1.0 / self.initial + (1.0 / self.last - 1.0 / self.initial) * progress)
+    }
+}
+
+// Temperatura jest podnoszona, gdy przyjmowanych pogorszeń jest mniej niż docelowo,
+// i obniżana, gdy więcej. Docelowy odsetek maleje liniowo od początkowego do zera.
+#[derive(Debug)]
+struct Adaptive {
+    temperature: f32,
+    minimum: f32,
+    initial_acceptance: f32,
+    progress: f32,
+    uphill: u32,
+    accepted: u32,
+}
+
+impl CoolingSchedule for Adaptive {
+    fn temperature(&mut self, progress: f32) -> f32 {
+        self.progress = progress;
+        self.temperature
+    }
+
+    fn record_uphill(&mut self, accepted: bool) {
+        self.uphill += 1;
+        self.accepted += accepted as u32;
+        if self.uphill < ADAPTIVE_WINDOW {
+            return;
+        }
+        let rate = self.accepted as f32 / self.uphill as f32;
+        let target = self.initial_acceptance * (1.0 - self.progress);
+        if rate < target {
+            self.temperature *= ADAPTIVE_FACTOR;
+        } else {
+            self.temperature = f32::max(self.minimum, self.temperature / ADAPTIVE_FACTOR);
+        }
+        self.uphill = 0;
+        self.accepted = 0;
+    }
+}
+
+pub fn schedule(
+    settings: &AnnealingSettings,
+    initial_temperature: f32,
+) -> Box<dyn CoolingSchedule> {
+    let last = settings.final_temperature;
+    // Wyznaczona temperatura początkowa może wyjść niższa od końcowej
+    let initial = f32::max(initial_temperature, last);
+    match settings.cooling {
+        CoolingKind::Geometric => Box::new(Geometric {
+            initial,
+            ratio: last / initial,
+        }),
+        CoolingKind::Linear => Box::new(Linear { initial, last }),
+        CoolingKind::Logarithmic => Box::new(Logarithmic {
+            initial,
+            c: (initial / last - 1.0) / (1.0 + LOG_SCALE).ln(),
+        }),
+        CoolingKind::LundyMees => Box::new(LundyMees { initial, last }),
+        CoolingKind::Adaptive => Box::new(Adaptive {
+            temperature: initial,
+            minimum: last,
+            initial_acceptance: settings.initial_acceptance,
+            progress: 0.0,
+            uphill: 0,
+            accepted: 0,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedules_cool_from_initial_to_final_temperature() {
+        for cooling in [
+            CoolingKind::Geometric,
+            CoolingKind::Linear,
+            CoolingKind::Logarithmic,
+            CoolingKind::LundyMees,
+        ] {
+            let settings = AnnealingSettings {
+                cooling,
+                final_temperature: 0.5,
+                ..Default::default()
+            };
+            let mut schedule = schedule(&settings, 20.0);
+            let temperatures = (0..=100)
+                .map(|x| schedule.temperature(x as f32 / 100.0))
+                .collect::<Vec<_>>();
+
+            assert!((temperatures[0] - 20.0).abs() < 1e-4, "{:?}", cooling);
+            assert!((temperatures[100] - 0.5).abs() < 1e-4, "{:?}", cooling);
+            assert!(
+                temperatures.windows(2).all(|x| x[1] < x[0]),
+                "{:?} is not decreasing",
+                cooling
+            );
+        }
+    }
+
+    #[test]
+    fn adaptive_schedule_follows_acceptance_rate() {
+        let settings = AnnealingSettings {
+            cooling: CoolingKind::Adaptive,
+            initial_acceptance: 0.5,
+            ..Default::default()
+        };
+        let mut schedule = schedule(&settings, 10.0);
+
+        // Odrzucane pogorszenia podnoszą temperaturę
+        assert_eq!(schedule.temperature(0.0), 10.0);
+        for _ in 0..ADAPTIVE_WINDOW {
+            schedule.record_uphill(false);
+        }
+        assert!(schedule.temperature(0.5) > 10.0);
+
+        // Przy połowie postępu celem jest 25% przyjętych
+        for x in 0..ADAPTIVE_WINDOW * 3 {
+            schedule.record_uphill(x % 2 == 0);
+        }
+        assert!(schedule.temperature(0.5) < 10.0);
+    }
+}
@@ -1,158 +1,1075 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    hash::Hash,
+};
 
-use crate::data_types::SimpleDate;
+use serde::Serialize;
 
-use super::annealing_buffer::AnnealingBuffer;
+use crate::{
+    data_types::{EnergyWeights, LessonLimits},
+    illegal_state::IllegalStateSubject,
+};
 
-#[derive(PartialEq, Eq, Hash)]
+use super::{
+    annealing_buffer::{AnnealingBuffer, Lesson, LessonShape},
+    inner_state::ChangedLessons,
+};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
 struct GroupDay {
     pub group: u8,
-    pub day: u8,
+    pub day: u16, // Dzień od początku semestru
 }
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
 struct TeacherDay {
     pub teacher: u8,
-    pub day: u8,
+    pub day: u16, // Dzień od początku semestru
+}
+
+// Ustawienia energii jednej grupy albo nauczyciela
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayParameters {
+    pub ideal_lessons_in_day: u8,
+    pub gap_weight: f32,
+    pub lessons_in_day_weight: f32,
+}
+
+// Preferencja czasowa z danych wejściowych zamieniona na jednostki czasu,
+// w których zajęcia grupy, nauczyciela albo sali są karane
+#[derive(Debug, Clone)]
+pub struct ResolvedTimePreference {
+    pub subject: IllegalStateSubject,
+    pub penalized: Vec<bool>, // Indeksem jest czas
+    pub weight: f32,
 }
 
-pub struct EnergyWeights {
-    pub student_gap_weight: f32,
-    pub teacher_gap_weight: f32,
-    pub teacher_lessons_in_day_weight: f32,
-    pub group_lessons_in_day_weight: f32,
+// Wagi energii z ustawieniami grup i nauczycieli wskazanych indeksami oraz
+// twarde ograniczenia liczby zajęć
+#[derive(Debug, Clone)]
+pub struct EnergyParameters {
+    pub weights: EnergyWeights,
+    pub ideal_lessons_in_day: u8,
+    pub group_overrides: HashMap<u8, DayParameters>,
+    pub teacher_overrides: HashMap<u8, DayParameters>,
+    // Kolejność jak w danych wejściowych
+    pub time_preferences: Vec<ResolvedTimePreference>,
+    pub group_limits: LessonLimits,
+    pub teacher_limits: LessonLimits,
+    // Ograniczenia już połączone z ogólnymi
+    pub group_limit_overrides: HashMap<u8, LessonLimits>,
+    pub teacher_limit_overrides: HashMap<u8, LessonLimits>,
+    // Liczba grup i nauczycieli, potrzebna do sprawdzenia minimum zajęć w dniach
+    // bez zajęć
+    pub group_count: usize,
+    pub teacher_count: usize,
+    // Czas lekcji w poprzednim planie (indeksowane id lekcji), pusty bez poprzedniego planu
+    pub previous_times: Vec<Option<u16>>,
 }
 
+impl Default for EnergyParameters {
+    fn default() -> Self {
+        Self::new(EnergyWeights::default(), 4)
+    }
+}
+
+impl EnergyParameters {
+    pub fn new(weights: EnergyWeights, ideal_lessons_in_day: u8) -> Self {
+        Self {
+            weights,
+            ideal_lessons_in_day,
+            group_overrides: HashMap::new(),
+            teacher_overrides: HashMap::new(),
+            time_preferences: Vec::new(),
+            group_limits: LessonLimits::default(),
+            teacher_limits: LessonLimits::default(),
+            group_limit_overrides: HashMap::new(),
+            teacher_limit_overrides: HashMap::new(),
+            group_count: 0,
+            teacher_count: 0,
+            previous_times: Vec::new(),
+        }
+    }
+
+    pub fn group(&self, group: u8) -> DayParameters {
+        self.group_overrides
+            .get(&group)
+            .copied()
+            .unwrap_or(DayParameters {
+                ideal_lessons_in_day: self.ideal_lessons_in_day,
+                gap_weight: self.weights.student_gap_weight,
+                lessons_in_day_weight: self.weights.group_lessons_in_day_weight,
+            })
+    }
+
+    pub fn teacher(&self, teacher: u8) -> DayParameters {
+        self.teacher_overrides
+            .get(&teacher)
+            .copied()
+            .unwrap_or(DayParameters {
+                ideal_lessons_in_day: self.ideal_lessons_in_day,
+                gap_weight: self.weights.teacher_gap_weight,
+                lessons_in_day_weight: self.weights.teacher_lessons_in_day_weight,
+            })
+    }
+
+    // Czy lekcja nie jest w terminie z poprzedniego planu
+    fn is_moved(&self, lesson_id: usize, lesson: Lesson) -> bool {
+        self.previous_times
+            .get(lesson_id)
+            .copied()
+            .flatten()
+            .is_some_and(|time| time != lesson.time)
+    }
+
+    pub fn group_limits(&self, group: u8) -> LessonLimits {
+        self.group_limit_overrides
+            .get(&group)
+            .copied()
+            .unwrap_or(self.group_limits)
+    }
+
+    pub fn teacher_limits(&self, teacher: u8) -> LessonLimits {
+        self.teacher_limit_overrides
+            .get(&teacher)
+            .copied()
+            .unwrap_or(self.teacher_limits)
+    }
+}
+
+// Ocena planu. Plan łamiący mniej twardych ograniczeń jest zawsze lepszy,
+// niezależnie od kary za miękkie ograniczenia (energii).
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Score {
+    pub hard: u32,
+    pub soft: f32,
+}
+
+impl Score {
+    pub fn is_feasible(&self) -> bool {
+        self.hard == 0
+    }
+}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.hard.cmp(&other.hard) {
+            Ordering::Equal => self.soft.partial_cmp(&other.soft),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+// Grupa, nauczyciel albo kurs, któremu naliczono karę
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PenaltySource {
+    GroupDay { group: u8, day: u16 },
+    TeacherDay { teacher: u8, day: u16 },
+    GroupSession { group: u8, session: u8 },
+    TeacherSession { teacher: u8, session: u8 },
+    Course(usize),
+    TimePreference(usize), // Indeks preferencji w danych wejściowych
+    MovedLesson(usize),    // Id lekcji
+}
+
+// Kara naliczona jednej grupie, nauczycielowi, kursowi albo preferencji
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Penalty {
+    pub source: PenaltySource,
+    pub raw: i64,
+    pub energy: f32,
+}
+
+// Jedna składowa energii wraz z tym, co się na nią złożyło
+#[derive(Debug)]
+pub struct EnergyTerm {
+    pub name: &'static str,
+    pub weight: f32, // Waga ogólna, grupy i nauczyciele mogą mieć własne
+    pub raw: i64,
+    pub energy: f32,
+    pub entries: Vec<Penalty>,
+}
+
+impl EnergyTerm {
+    fn new(name: &'static str, weight: f32, mut entries: Vec<Penalty>) -> Self {
+        entries.retain(|x| x.raw != 0);
+        // Największe kary najpierw
+        entries.sort_by(|a, b| {
+            b.energy
+                .total_cmp(&a.energy)
+                .then(b.raw.cmp(&a.raw))
+                .then(a.source.cmp(&b.source))
+        });
+        Self {
+            name,
+            weight,
+            raw: entries.iter().map(|x| x.raw).sum(),
+            energy: entries.iter().fold(0.0, |sum, x| sum + x.energy),
+            entries,
+        }
+    }
+}
+
+// Składowa energii przed przemnożeniem przez wagi (`raw`) i po nim
+#[derive(Default, Debug, Clone, Copy)]
+struct Term {
+    raw: i64,
+    energy: f64,
+}
+
+impl Term {
+    fn add(&mut self, raw: i64, weight: f32) {
+        self.raw += raw;
+        self.energy += raw as f64 * weight as f64;
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+struct Terms {
+    group_gaps: Term,
+    teacher_gaps: Term,
+    group_lessons_in_day: Term,
+    teacher_lessons_in_day: Term,
+    session_balance: Term,
+    pattern_stability: Term,
+    time_preferences: Term,
+    stability: Term,
+}
+
+impl Terms {
+    fn all(&self) -> [Term; 8] {
+        [
+            self.group_gaps,
+            self.teacher_gaps,
+            self.group_lessons_in_day,
+            self.teacher_lessons_in_day,
+            self.session_balance,
+            self.pattern_stability,
+            self.time_preferences,
+            self.stability,
+        ]
+    }
+
+    fn energy(&self) -> f64 {
+        self.all().iter().map(|x| x.energy).sum()
+    }
+
+    // Energia liczona przyrostowo może się różnić od policzonej od nowa
+    // tylko o błędy zaokrągleń
+    fn assert_matches(&self, other: &Terms) {
+        for (left, right) in self.all().iter().zip(other.all().iter()) {
+            assert_eq!(
+                left.raw, right.raw,
+                "Incremental energy differs from full recomputation"
+            );
+            assert!(
+                (left.energy - right.energy).abs() <= 1e-6 * right.energy.abs().max(1.0),
+                "Incremental energy differs from full recomputation: {} != {}",
+                left.energy,
+                right.energy
+            );
+        }
+    }
+}
+
+// Statystyki planu aktualizowane przy każdej mutacji tylko dla zmienionych lekcji.
+// Jednostki czasu zajęte przez grupę lub nauczyciela w ciągu dnia są zapisane jako
+// maska bitowa numerów zajęć w dniu (`Slot::index`).
 #[derive(Default)]
 pub struct BufferStatistics {
-    last_lesson_of_group: HashMap<u8, u8>,
-    group_gaps: u8,
-    last_lesson_of_teacher: HashMap<u8, u8>,
-    teacher_gaps: u8,
+    parameters: EnergyParameters,
 
-    group_lessons_in_day: HashMap<GroupDay, u8>,
-    teacher_lessons_in_day: HashMap<TeacherDay, u8>,
+    // Stan lekcji uwzględniony w statystykach
+    lessons: Vec<Lesson>,
+    placed: Vec<bool>,
 
-    max_day: u8,
-    max_group: u8,
-    max_teacher: u8,
+    group_days: HashMap<GroupDay, u128>,
+    teacher_days: HashMap<TeacherDay, u128>,
+
+    // Liczba lekcji kursu w kolejnych zjazdach
+    course_lessons_in_session: HashMap<usize, Vec<u16>>,
+    // Ile lekcji kursu zajmuje dane miejsce w planie zjazdu (dzień, numer zajęć)
+    course_positions: HashMap<usize, HashMap<(u8, u8), u16>>,
+    sessions: u8,
+    // Liczba dni we wszystkich zjazdach
+    days: u16,
+
+    // Jednostki czasu zajęć niezgodne z kolejnymi preferencjami
+    preference_penalties: Vec<i64>,
+
+    // Lekcje przesunięte względem poprzedniego planu
+    moved_lessons: HashSet<usize>,
+
+    // Liczba jednostek czasu zajęć grupy i nauczyciela w zjeździe
+    group_sessions: HashMap<(u8, u8), u32>,
+    teacher_sessions: HashMap<(u8, u8), u32>,
+
+    terms: Terms,
+    unplaced_lessons: u32,
+    // Naruszenia twardych ograniczeń liczby zajęć w dniu i w zjeździe
+    day_limit_violations: i64,
+    session_limit_violations: i64,
+}
+
+// Okienka to wolne jednostki czasu między pierwszą a ostatnią lekcją w dniu
+fn day_gaps(mask: u128) -> i64 {
+    if mask == 0 {
+        return 0;
+    }
+    let span = 128 - mask.leading_zeros() - mask.trailing_zeros();
+    (span - mask.count_ones()) as i64
+}
+
+// Odchylenie od idealnej liczby jednostek czasu zajęć w dniu
+fn day_deviation(mask: u128, ideal: u8) -> i64 {
+    if mask == 0 {
+        return 0;
+    }
+    (ideal as i64 - mask.count_ones() as i64).abs()
+}
+
+// Zmienia maskę dnia i zwraca zmianę okienek, odchylenia od idealnej liczby zajęć
+// i naruszeń ograniczeń liczby zajęć w dniu
+fn toggle_day<K: Hash + Eq>(
+    days: &mut HashMap<K, u128>,
+    key: K,
+    bit: u128,
+    ideal: u8,
+    limits: LessonLimits,
+) -> (i64, i64, i64) {
+    let (before, after) = match days.entry(key) {
+        Entry::Occupied(mut entry) => {
+            let before = *entry.get();
+            let after = before ^ bit;
+            if after == 0 {
+                entry.remove();
+            } else {
+                entry.insert(after);
+            }
+            (before, after)
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(bit);
+            (0, bit)
+        }
+    };
+    (
+        day_gaps(after) - day_gaps(before),
+        day_deviation(after, ideal) - day_deviation(before, ideal),
+        limits.day_violations(after.count_ones()) as i64
+            - limits.day_violations(before.count_ones()) as i64,
+    )
+}
+
+// Dodaje albo usuwa jednostkę czasu zajęć w zjeździe i zwraca zmianę naruszeń
+// ograniczenia liczby zajęć w zjeździe
+fn toggle_session<K: Hash + Eq>(
+    sessions: &mut HashMap<K, u32>,
+    key: K,
+    add: bool,
+    limits: LessonLimits,
+) -> i64 {
+    let count = sessions.entry(key).or_insert(0);
+    let before = *count;
+    if add {
+        *count += 1;
+    } else {
+        *count -= 1;
+    }
+    limits.session_violations(*count) as i64 - limits.session_violations(before) as i64
 }
 
 impl BufferStatistics {
-    pub fn new() -> Self {
+    pub fn new(parameters: EnergyParameters) -> Self {
         Self {
+            parameters,
             ..Default::default()
         }
     }
 
     pub fn emplace_of_buffer(&mut self, buffer: &AnnealingBuffer) {
         self.clear();
+        self.sessions = buffer.slots.sessions();
+        self.days = buffer.slots.day_count();
+        self.preference_penalties = vec![0; self.parameters.time_preferences.len()];
 
-        let mut lesson_buffer = buffer.inner_state.state_ref().lessons.clone();
-        lesson_buffer.sort_by_key(|l| l.time);
-
-        for lesson in lesson_buffer.iter() {
-            // Lekcje w ciągu dnia
-            {
-                let teacher = lesson.teacher;
-                let group = lesson.group;
-                let day = SimpleDate::from_u8_time(lesson.time).day;
-
-                self.max_day = self.max_day.max(day);
-                self.max_group = self.max_group.max(group);
-                self.max_teacher = self.max_teacher.max(teacher);
-
-                if let Some(current) = self
-                    .teacher_lessons_in_day
-                    .remove(&TeacherDay { teacher, day })
-                {
-                    self.teacher_lessons_in_day
-                        .insert(TeacherDay { teacher, day }, current + 1);
-                } else {
-                    self.teacher_lessons_in_day
-                        .insert(TeacherDay { teacher, day }, 1);
-                }
+        // Dni bez zajęć naruszają minimum liczby zajęć w dniu. Dodawane lekcje
+        // zmieniają naruszenia względem dnia pustego.
+        self.day_limit_violations = self
+            .days_with_minimum()
+            .map(|(_, limits)| limits.day_violations(0) as i64)
+            .sum();
 
-                if let Some(current) = self.group_lessons_in_day.remove(&GroupDay { group, day }) {
-                    self.group_lessons_in_day
-                        .insert(GroupDay { group, day }, current + 1);
-                } else {
-                    self.group_lessons_in_day.insert(GroupDay { group, day }, 1);
-                }
+        let state_ref = buffer.inner_state.state_ref();
+        self.lessons = state_ref.lessons.clone();
+        self.placed = state_ref.placed.clone();
+        for (lesson_id, &lesson) in state_ref.lessons.iter().enumerate() {
+            if state_ref.placed[lesson_id] {
+                let shape = &state_ref.lesson_shapes[lesson_id];
+                self.toggle_lesson(buffer, lesson_id, lesson, shape, true);
+            } else {
+                self.unplaced_lessons += 1;
             }
+        }
+    }
 
-            // Okienka studentów
-            if let Some(last_group_lesson) =
-                self.last_lesson_of_group.insert(lesson.group, lesson.time)
-            {
-                // Okienka liczą się tylko w tym samym dniu
-                if SimpleDate::from_u8_time(last_group_lesson).day
-                    == SimpleDate::from_u8_time(lesson.time).day
-                {
-                    let gap = lesson.time - last_group_lesson;
-                    self.group_gaps += gap;
-                }
-            }
+    // Uwzględnia nowy stan zmienionych lekcji i zwraca zmianę energii
+    pub fn update(&mut self, buffer: &AnnealingBuffer, changed: ChangedLessons) -> f32 {
+        let before = self.terms.energy();
+        let state_ref = buffer.inner_state.state_ref();
 
-            // Okienka nauczycieli
-            if let Some(last_teacher_lesson) = self
-                .last_lesson_of_teacher
-                .insert(lesson.teacher, lesson.time)
-            {
-                // Okienka liczą się tylko w tym samym dniu
-                if SimpleDate::from_u8_time(last_teacher_lesson).day
-                    == SimpleDate::from_u8_time(lesson.time).day
-                {
-                    let gap = lesson.time - last_teacher_lesson;
-                    self.teacher_gaps += gap;
-                }
+        // Najpierw usuwane są wszystkie stare stany, bo zamienione lekcje mogą
+        // zajmować nawzajem swoje jednostki czasu. Lekcja może też zostać
+        // umieszczona w planie albo z niego usunięta.
+        for lesson_id in changed.iter() {
+            let shape = &state_ref.lesson_shapes[lesson_id];
+            if self.placed[lesson_id] {
+                self.toggle_lesson(buffer, lesson_id, self.lessons[lesson_id], shape, false);
+            } else {
+                self.unplaced_lessons -= 1;
             }
         }
+        for lesson_id in changed.iter() {
+            let shape = &state_ref.lesson_shapes[lesson_id];
+            let lesson = state_ref.lessons[lesson_id];
+            let placed = state_ref.placed[lesson_id];
+            if placed {
+                self.toggle_lesson(buffer, lesson_id, lesson, shape, true);
+            } else {
+                self.unplaced_lessons += 1;
+            }
+            self.lessons[lesson_id] = lesson;
+            self.placed[lesson_id] = placed;
+        }
+
+        if cfg!(debug_assertions) {
+            let mut full = BufferStatistics::new(self.parameters.clone());
+            full.emplace_of_buffer(buffer);
+            self.terms.assert_matches(&full.terms);
+            assert_eq!(
+                self.hard_violations(),
+                full.hard_violations(),
+                "Incremental hard violations differ from full recomputation"
+            );
+        }
+
+        (self.terms.energy() - before) as f32
     }
 
-    fn gaps_energy(&self) -> (f32, f32) {
-        let mut group_energy = 0.0;
-        let mut teacher_energy = 0.0;
+    // Dodaje (`add`) albo usuwa wkład lekcji w statystyki
+    fn toggle_lesson(
+        &mut self,
+        buffer: &AnnealingBuffer,
+        lesson_id: usize,
+        lesson: Lesson,
+        shape: &LessonShape,
+        add: bool,
+    ) {
+        // Lekcje w ciągu dnia i okienka
+        for time in lesson.time..lesson.time + shape.duration as u16 {
+            let day = buffer.slots.day(time);
+            let slot = buffer.slots.slot(time);
+            let bit = 1u128 << slot.index;
 
-        for day in 0..self.max_day {
-            for group in 0..self.max_group {
-                if let Some(lessons) = self.group_lessons_in_day.get(&GroupDay { group, day }) {
-                    let diff_from_perfect = (4.0 - *lessons as f32).abs();
-                    group_energy += diff_from_perfect;
-                }
+            let teacher = self.parameters.teacher(lesson.teacher);
+            let limits = self.parameters.teacher_limits(lesson.teacher);
+            let (gaps, deviation, violations) = toggle_day(
+                &mut self.teacher_days,
+                TeacherDay {
+                    teacher: lesson.teacher,
+                    day,
+                },
+                bit,
+                teacher.ideal_lessons_in_day,
+                limits,
+            );
+            self.terms.teacher_gaps.add(gaps, teacher.gap_weight);
+            self.terms
+                .teacher_lessons_in_day
+                .add(deviation, teacher.lessons_in_day_weight);
+            self.day_limit_violations += violations;
+            self.session_limit_violations += toggle_session(
+                &mut self.teacher_sessions,
+                (lesson.teacher, slot.session),
+                add,
+                limits,
+            );
+
+            for &group in &shape.groups {
+                let parameters = self.parameters.group(group);
+                let limits = self.parameters.group_limits(group);
+                let (gaps, deviation, violations) = toggle_day(
+                    &mut self.group_days,
+                    GroupDay { group, day },
+                    bit,
+                    parameters.ideal_lessons_in_day,
+                    limits,
+                );
+                self.terms.group_gaps.add(gaps, parameters.gap_weight);
+                self.terms
+                    .group_lessons_in_day
+                    .add(deviation, parameters.lessons_in_day_weight);
+                self.day_limit_violations += violations;
+                self.session_limit_violations +=
+                    toggle_session(&mut self.group_sessions, (group, slot.session), add, limits);
             }
-            for teacher in 0..self.max_teacher {
-                if let Some(lessons) = self
-                    .teacher_lessons_in_day
-                    .get(&TeacherDay { teacher, day })
-                {
-                    let diff_from_perfect = (4.0 - *lessons as f32).abs();
-                    teacher_energy += diff_from_perfect;
-                }
+        }
+
+        // Rozkład kursu na zjazdy
+        let course = shape.course;
+        let (balance_before, stability_before) = self.course_energy(course);
+        let sessions = self.sessions as usize;
+        let session = buffer.slots.slot(lesson.time).session as usize;
+        let position = buffer.slots.position(lesson.time);
+        let counts = self
+            .course_lessons_in_session
+            .entry(course)
+            .or_insert_with(|| vec![0; sessions]);
+        let positions = self.course_positions.entry(course).or_default();
+        if add {
+            counts[session] += 1;
+            *positions.entry(position).or_insert(0) += 1;
+        } else {
+            counts[session] -= 1;
+            let count = positions.get_mut(&position).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                positions.remove(&position);
+            }
+        }
+        let (balance_after, stability_after) = self.course_energy(course);
+        let weights = &self.parameters.weights;
+        self.terms.session_balance.add(
+            balance_after - balance_before,
+            weights.session_balance_weight,
+        );
+        self.terms.pattern_stability.add(
+            stability_after - stability_before,
+            weights.pattern_stability_weight,
+        );
+
+        let sign = if add { 1 } else { -1 };
+
+        // Stabilność względem poprzedniego planu
+        if self.parameters.is_moved(lesson_id, lesson) {
+            if add {
+                self.moved_lessons.insert(lesson_id);
+            } else {
+                self.moved_lessons.remove(&lesson_id);
+            }
+            self.terms.stability.add(sign, weights.stability_weight);
+        }
+
+        // Preferencje czasowe
+        let times = lesson.time as usize..lesson.time as usize + shape.duration as usize;
+        for (id, preference) in self.parameters.time_preferences.iter().enumerate() {
+            if !preference.subject.is_part_of(lesson, shape) {
+                continue;
             }
+            let penalty = preference.penalized[times.clone()]
+                .iter()
+                .filter(|&&x| x)
+                .count() as i64
+                * sign;
+            self.preference_penalties[id] += penalty;
+            self.terms
+                .time_preferences
+                .add(penalty, preference.weight * weights.time_preference_weight);
         }
+    }
+
+    // Lekcje kursu powinny być rozłożone równo na zjazdy (liczba lekcji w zjeździe
+    // może się różnić od średniej o mniej niż jeden) i odbywać się w tych samych
+    // miejscach planu w każdym zjeździe. Kurs mający najwięcej `n` lekcji w jednym
+    // zjeździe potrzebuje co najmniej `n` różnych miejsc, każde kolejne jest karane.
+    fn course_energy(&self, course: usize) -> (i64, i64) {
+        let counts = match self.course_lessons_in_session.get(&course) {
+            Some(x) => x,
+            None => return (0, 0),
+        };
+
+        let sessions = self.sessions as u16;
+        let total = counts.iter().sum::<u16>();
+        let min = total / sessions;
+        let max = total.div_ceil(sessions);
+        let balance = counts
+            .iter()
+            .map(|&count| (count.saturating_sub(max) + min.saturating_sub(count)) as i64)
+            .sum();
+
+        let max_in_session = counts.iter().copied().max().unwrap_or(0);
+        let stability = self.course_positions[&course].len() as i64 - max_in_session as i64;
+
+        (balance, stability)
+    }
+
+    pub fn energy(&self) -> f32 {
+        self.terms.energy() as f32
+    }
+
+    // Składowe energii w tej samej kolejności co w `Terms`
+    pub fn breakdown(&self) -> Vec<EnergyTerm> {
+        let parameters = &self.parameters;
+        let weights = &parameters.weights;
+        let group_days = || {
+            self.group_days.iter().map(|(key, &mask)| {
+                let source = PenaltySource::GroupDay {
+                    group: key.group,
+                    day: key.day,
+                };
+                (source, parameters.group(key.group), mask)
+            })
+        };
+        let teacher_days = || {
+            self.teacher_days.iter().map(|(key, &mask)| {
+                let source = PenaltySource::TeacherDay {
+                    teacher: key.teacher,
+                    day: key.day,
+                };
+                (source, parameters.teacher(key.teacher), mask)
+            })
+        };
+        let courses = || {
+            self.course_lessons_in_session
+                .keys()
+                .map(|&course| (PenaltySource::Course(course), self.course_energy(course)))
+        };
+        let penalty = |source, raw: i64, weight: f32| Penalty {
+            source,
+            raw,
+            energy: raw as f32 * weight,
+        };
+
+        vec![
+            EnergyTerm::new(
+                "group_gaps",
+                weights.student_gap_weight,
+                group_days()
+                    .map(|(x, day, mask)| penalty(x, day_gaps(mask), day.gap_weight))
+                    .collect(),
+            ),
+            EnergyTerm::new(
+                "teacher_gaps",
+                weights.teacher_gap_weight,
+                teacher_days()
+                    .map(|(x, day, mask)| penalty(x, day_gaps(mask), day.gap_weight))
+                    .collect(),
+            ),
+            EnergyTerm::new(
+                "group_lessons_in_day",
+                weights.group_lessons_in_day_weight,
+                group_days()
+                    .map(|(x, day, mask)| {
+                        let deviation = day_deviation(mask, day.ideal_lessons_in_day);
+                        penalty(x, deviation, day.lessons_in_day_weight)
+                    })
+                    .collect(),
+            ),
+            EnergyTerm::new(
+                "teacher_lessons_in_day",
+                weights.teacher_lessons_in_day_weight,
+                teacher_days()
+                    .map(|(x, day, mask)| {
+                        let deviation = day_deviation(mask, day.ideal_lessons_in_day);
+                        penalty(x, deviation, day.lessons_in_day_weight)
+                    })
+                    .collect(),
+            ),
+            EnergyTerm::new(
+                "session_balance",
+                weights.session_balance_weight,
+                courses()
+                    .map(|(x, (balance, _))| penalty(x, balance, weights.session_balance_weight))
+                    .collect(),
+            ),
+            EnergyTerm::new(
+                "pattern_stability",
+                weights.pattern_stability_weight,
+                courses()
+                    .map(|(x, (_, stability))| {
+                        penalty(x, stability, weights.pattern_stability_weight)
+                    })
+                    .collect(),
+            ),
+            EnergyTerm::new(
+                "time_preferences",
+                weights.time_preference_weight,
+                parameters
+                    .time_preferences
+                    .iter()
+                    .zip(&self.preference_penalties)
+                    .enumerate()
+                    .map(|(id, (preference, &raw))| {
+                        penalty(
+                            PenaltySource::TimePreference(id),
+                            raw,
+                            preference.weight * weights.time_preference_weight,
+                        )
+                    })
+                    .collect(),
+            ),
+            EnergyTerm::new(
+                "stability",
+                weights.stability_weight,
+                self.moved_lessons
+                    .iter()
+                    .map(|&id| penalty(PenaltySource::MovedLesson(id), 1, weights.stability_weight))
+                    .collect(),
+            ),
+        ]
+    }
+
+    // Naruszenia twardych ograniczeń liczby zajęć w dniu i w zjeździe
+    pub fn hard_breakdown(&self) -> Vec<EnergyTerm> {
+        let parameters = &self.parameters;
+        let violations = |source, raw: u32| Penalty {
+            source,
+            raw: raw as i64,
+            energy: raw as f32,
+        };
+        let group_days = self.group_days.iter().map(|(key, &mask)| {
+            let limits = parameters.group_limits(key.group);
+            let source = PenaltySource::GroupDay {
+                group: key.group,
+                day: key.day,
+            };
+            violations(source, limits.day_violations(mask.count_ones()))
+        });
+        let teacher_days = self.teacher_days.iter().map(|(key, &mask)| {
+            let limits = parameters.teacher_limits(key.teacher);
+            let source = PenaltySource::TeacherDay {
+                teacher: key.teacher,
+                day: key.day,
+            };
+            violations(source, limits.day_violations(mask.count_ones()))
+        });
+        // Dni bez zajęć nie mają maski
+        let empty_days = self
+            .days_with_minimum()
+            .filter(|(source, _)| match *source {
+                PenaltySource::GroupDay { group, day } => {
+                    !self.group_days.contains_key(&GroupDay { group, day })
+                }
+                PenaltySource::TeacherDay { teacher, day } => {
+                    !self.teacher_days.contains_key(&TeacherDay { teacher, day })
+                }
+                _ => unreachable!(),
+            })
+            .map(|(source, limits)| violations(source, limits.day_violations(0)));
+        let group_sessions = self
+            .group_sessions
+            .iter()
+            .map(|(&(group, session), &count)| {
+                let limits = parameters.group_limits(group);
+                let source = PenaltySource::GroupSession { group, session };
+                violations(source, limits.session_violations(count))
+            });
+        let teacher_sessions = self
+            .teacher_sessions
+            .iter()
+            .map(|(&(teacher, session), &count)| {
+                let limits = parameters.teacher_limits(teacher);
+                let source = PenaltySource::TeacherSession { teacher, session };
+                violations(source, limits.session_violations(count))
+            });
 
-        (group_energy, teacher_energy)
+        vec![
+            EnergyTerm::new(
+                "lessons_in_day_limits",
+                1.0,
+                group_days.chain(teacher_days).chain(empty_days).collect(),
+            ),
+            EnergyTerm::new(
+                "lessons_in_session_limits",
+                1.0,
+                group_sessions.chain(teacher_sessions).collect(),
+            ),
+        ]
     }
 
-    pub fn energy(&self, energy_weights: &EnergyWeights) -> f32 {
-        let group_gap_energy = self.group_gaps as f32 * energy_weights.student_gap_weight;
-        let student_gap_energy = self.teacher_gaps as f32 * energy_weights.teacher_gap_weight;
-        let (raw_group_lessons_in_day_energy, raw_teacher_lessons_in_day_energy) =
-            self.gaps_energy();
+    // Wszystkie dni grup i nauczycieli, które mają minimum zajęć w dniu, wraz
+    // z ich ograniczeniami
+    fn days_with_minimum(&self) -> impl Iterator<Item = (PenaltySource, LessonLimits)> + '_ {
+        let parameters = &self.parameters;
+        let days = self.days;
+        let groups = (0..parameters.group_count)
+            .map(move |group| (group as u8, parameters.group_limits(group as u8)))
+            .filter(|(_, limits)| limits.min_lessons_in_day.is_some())
+            .flat_map(move |(group, limits)| {
+                (0..days).map(move |day| (PenaltySource::GroupDay { group, day }, limits))
+            });
+        let teachers = (0..parameters.teacher_count)
+            .map(move |teacher| (teacher as u8, parameters.teacher_limits(teacher as u8)))
+            .filter(|(_, limits)| limits.min_lessons_in_day.is_some())
+            .flat_map(move |(teacher, limits)| {
+                (0..days).map(move |day| (PenaltySource::TeacherDay { teacher, day }, limits))
+            });
+        groups.chain(teachers)
+    }
+
+    pub fn unplaced_lessons(&self) -> u32 {
+        self.unplaced_lessons
+    }
+
+    // Lekcje, których nie udało się umieścić w planie, i jednostki czasu zajęć
+    // ponad limity (albo brakujące do minimum)
+    pub fn hard_violations(&self) -> u32 {
+        self.unplaced_lessons + (self.day_limit_violations + self.session_limit_violations) as u32
+    }
 
-        group_gap_energy
-            + student_gap_energy
-            + raw_group_lessons_in_day_energy * energy_weights.teacher_lessons_in_day_weight
-            + raw_teacher_lessons_in_day_energy * energy_weights.teacher_lessons_in_day_weight
+    pub fn score(&self) -> Score {
+        Score {
+            hard: self.hard_violations(),
+            soft: self.energy(),
+        }
     }
 
     pub fn clear(&mut self) {
-        self.last_lesson_of_teacher.clear();
-        self.last_lesson_of_group.clear();
-        self.group_lessons_in_day.clear();
-        self.teacher_lessons_in_day.clear();
-        self.group_gaps = 0;
-        self.teacher_gaps = 0;
+        self.lessons.clear();
+        self.placed.clear();
+        self.group_days.clear();
+        self.teacher_days.clear();
+        self.course_lessons_in_session.clear();
+        self.course_positions.clear();
+        self.preference_penalties.clear();
+        self.moved_lessons.clear();
+        self.group_sessions.clear();
+        self.teacher_sessions.clear();
+        self.terms = Terms::default();
+        self.unplaced_lessons = 0;
+        self.day_limit_violations = 0;
+        self.session_limit_violations = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        annealing::mutation::{Mutation, MutationType},
+        data_types::SlotTable,
+    };
+
+    fn parameters() -> EnergyParameters {
+        let weights = EnergyWeights {
+            student_gap_weight: 1.0,
+            teacher_gap_weight: 1.0,
+            teacher_lessons_in_day_weight: 1.0,
+            group_lessons_in_day_weight: 1.0,
+            session_balance_weight: 1.0,
+            pattern_stability_weight: 1.0,
+            time_preference_weight: 1.0,
+            stability_weight: 1.0,
+        };
+        EnergyParameters::new(weights, 4)
+    }
+
+    #[test]
+    fn gaps_count_free_slots_between_lessons() {
+        assert_eq!(day_gaps(0b1), 0);
+        assert_eq!(day_gaps(0b11), 0);
+        assert_eq!(day_gaps(0b1001), 2);
+        assert_eq!(day_deviation(0b1001, 4), 2);
+        assert_eq!(day_deviation(0b1001, 1), 1);
+        assert_eq!(day_deviation(0, 4), 0);
+    }
+
+    #[test]
+    fn hard_violations_outweigh_energy() {
+        let feasible = Score {
+            hard: 0,
+            soft: 100.0,
+        };
+        let infeasible = Score { hard: 1, soft: 0.0 };
+        assert!(feasible < infeasible);
+        assert!(Score { hard: 1, soft: 1.0 } > infeasible);
+        assert!(!infeasible.is_feasible());
+    }
+
+    #[test]
+    fn breakdown_sums_up_to_energy() {
+        let slots = SlotTable::new(&Default::default(), 1, 1).unwrap();
+        let mut buffer = AnnealingBuffer::new(vec![LessonShape::new(vec![0], 1, 0); 2], slots);
+        assert!(buffer.place_lesson(0, 0, 0, 0));
+        assert!(buffer.place_lesson(1, 1, 0, 3));
+
+        let mut statistics = BufferStatistics::new(parameters());
+        statistics.emplace_of_buffer(&buffer);
+        let breakdown = statistics.breakdown();
+
+        assert_eq!(
+            breakdown.iter().map(|x| x.energy).sum::<f32>(),
+            statistics.energy()
+        );
+        let group_gaps = &breakdown[0];
+        assert_eq!(group_gaps.raw, 2);
+        assert_eq!(
+            group_gaps.entries,
+            vec![Penalty {
+                source: PenaltySource::GroupDay { group: 0, day: 0 },
+                raw: 2,
+                energy: 2.0,
+            }]
+        );
+        // Każdy nauczyciel ma jedne zajęcia zamiast czterech
+        assert_eq!(breakdown[3].entries.len(), 2);
+        assert_eq!(breakdown[3].raw, 6);
+    }
+
+    #[test]
+    fn group_and_teacher_overrides_replace_global_parameters() {
+        let slots = SlotTable::new(&Default::default(), 1, 1).unwrap();
+        let mut buffer = AnnealingBuffer::new(vec![LessonShape::new(vec![0], 1, 0); 2], slots);
+        assert!(buffer.place_lesson(0, 0, 0, 0));
+        assert!(buffer.place_lesson(1, 0, 0, 3));
+
+        let mut parameters = parameters();
+        parameters.group_overrides.insert(
+            0,
+            DayParameters {
+                ideal_lessons_in_day: 2,
+                gap_weight: 3.0,
+                lessons_in_day_weight: 1.0,
+            },
+        );
+        let mut statistics = BufferStatistics::new(parameters);
+        statistics.emplace_of_buffer(&buffer);
+        let breakdown = statistics.breakdown();
+
+        // Okienko dwóch jednostek czasu z wagą grupy, nauczyciel z ogólną wagą
+        assert_eq!(breakdown[0].raw, 2);
+        assert_eq!(breakdown[0].energy, 6.0);
+        assert_eq!(breakdown[1].energy, 2.0);
+        // Grupa ma tyle zajęć, ile chce, nauczyciel o dwie za mało
+        assert_eq!(breakdown[2].raw, 0);
+        assert_eq!(breakdown[3].raw, 2);
+        assert_eq!(statistics.energy(), 10.0);
+    }
+
+    #[test]
+    fn lessons_over_limits_are_hard_violations() {
+        let slots = SlotTable::new(&Default::default(), 2, 1).unwrap();
+        let mut buffer = AnnealingBuffer::new(vec![LessonShape::new(vec![0], 1, 0); 3], slots);
+        assert!(buffer.place_lesson(0, 0, 0, 0));
+        assert!(buffer.place_lesson(1, 1, 0, 1));
+        assert!(buffer.place_lesson(2, 0, 0, 6));
+
+        let mut parameters = parameters();
+        parameters.group_limits.max_lessons_in_day = Some(1);
+        parameters.teacher_limit_overrides.insert(
+            0,
+            LessonLimits {
+                max_lessons_in_session: Some(1),
+                ..Default::default()
+            },
+        );
+        let mut statistics = BufferStatistics::new(parameters);
+        statistics.emplace_of_buffer(&buffer);
+
+        // Grupa ma dwoje zajęć w pierwszym dniu, nauczyciel 0 dwoje w zjeździe
+        assert_eq!(statistics.hard_violations(), 2);
+        let breakdown = statistics.hard_breakdown();
+        assert_eq!(
+            breakdown[0].entries[0].source,
+            PenaltySource::GroupDay { group: 0, day: 0 }
+        );
+        assert_eq!(
+            breakdown[1].entries[0].source,
+            PenaltySource::TeacherSession {
+                teacher: 0,
+                session: 0
+            }
+        );
+    }
+
+    #[test]
+    fn empty_days_violate_minimum() {
+        let slots = SlotTable::new(&Default::default(), 2, 1).unwrap();
+        let mut buffer = AnnealingBuffer::new(vec![LessonShape::new(vec![0], 1, 0); 2], slots);
+        assert!(buffer.place_lesson(0, 0, 0, 0));
+        assert!(buffer.place_lesson(1, 0, 0, 1));
+
+        let mut parameters = parameters();
+        parameters.group_count = 1;
+        parameters.teacher_count = 1;
+        parameters.group_limits.min_lessons_in_day = Some(2);
+        let mut statistics = BufferStatistics::new(parameters);
+        statistics.emplace_of_buffer(&buffer);
+
+        // Drugi dzień grupy jest wolny, nauczyciel nie ma minimum
+        assert_eq!(statistics.hard_violations(), 2);
+        assert_eq!(
+            statistics.hard_breakdown()[0].entries,
+            vec![Penalty {
+                source: PenaltySource::GroupDay { group: 0, day: 1 },
+                raw: 2,
+                energy: 2.0,
+            }]
+        );
+
+        // Po jednej lekcji w każdym dniu
+        let changed = buffer
+            .inner_state
+            .apply_mutation(Mutation::new(1, MutationType::ChangeTime(6)), None)
+            .unwrap();
+        statistics.update(&buffer, changed);
+        assert_eq!(statistics.hard_violations(), 2);
+        assert_eq!(statistics.hard_breakdown()[0].entries.len(), 2);
+    }
+
+    #[test]
+    fn update_matches_full_recomputation() {
+        let slots = SlotTable::new(&Default::default(), 2, 2).unwrap();
+        let mut buffer = AnnealingBuffer::new(
+            vec![
+                LessonShape::new(vec![0], 1, 0),
+                LessonShape::new(vec![0], 2, 1),
+                LessonShape::new(vec![0, 1], 1, 2),
+            ],
+            slots,
+        );
+        assert!(buffer.place_lesson(0, 0, 0, 0));
+        assert!(buffer.place_lesson(1, 0, 0, 3));
+        assert!(buffer.place_lesson(2, 1, 1, 14));
+
+        // Grupa 0 woli nie mieć zajęć w pierwszym dniu
+        let mut parameters = parameters();
+        parameters.time_preferences.push(ResolvedTimePreference {
+            subject: IllegalStateSubject::StudentGroup(0),
+            penalized: (0..24).map(|time| time < 6).collect(),
+            weight: 2.0,
+        });
+        parameters.group_limits = LessonLimits {
+            min_lessons_in_day: Some(2),
+            max_lessons_in_day: Some(2),
+            max_lessons_in_session: Some(4),
+        };
+        parameters.group_count = 2;
+        parameters.teacher_count = 2;
+        // Lekcja 2 była wcześniej w innym terminie, lekcja 1 w obecnym
+        parameters.previous_times = vec![None, Some(3), Some(20)];
+        let mut statistics = BufferStatistics::new(parameters.clone());
+        statistics.emplace_of_buffer(&buffer);
+        assert_eq!(statistics.preference_penalties, vec![3]);
+        assert_eq!(statistics.moved_lessons.len(), 1);
+
+        // Zamiana lekcji 0 i 1, przesunięcie w obrębie dnia i do innego zjazdu
+        for (lesson, time) in [(0, 3), (2, 5), (1, 20)] {
+            let energy = statistics.energy();
+            let changed = buffer
+                .inner_state
+                .apply_mutation(Mutation::new(lesson, MutationType::ChangeTime(time)), None)
+                .unwrap();
+            let delta = statistics.update(&buffer, changed);
+
+            let mut full = BufferStatistics::new(parameters.clone());
+            full.emplace_of_buffer(&buffer);
+            statistics.terms.assert_matches(&full.terms);
+            assert_eq!(statistics.preference_penalties, full.preference_penalties);
+            assert_eq!(statistics.hard_violations(), full.hard_violations());
+            assert_eq!(statistics.moved_lessons, full.moved_lessons);
+            assert_eq!(full.energy(), energy + delta);
+        }
     }
 }
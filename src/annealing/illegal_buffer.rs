@@ -1,19 +1,24 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use crate::illegal_state::IllegalState;
+#[cfg(test)]
+use itertools::iproduct;
 
-use super::inner_state::Lesson;
+use crate::illegal_state::{IllegalState, IllegalStateSubject};
+
+use crate::data_types::SlotTable;
+
+use super::inner_state::{Lesson, LessonShape};
 
 #[derive(Hash, Clone, Copy, PartialEq, Eq)]
 pub struct CanTeach {
-    pub lesson_id: u8,
+    pub lesson_id: usize,
     pub teacher_id: u8,
 }
 
 #[derive(Hash, Clone, Copy, PartialEq, Eq)]
 pub struct CanHold {
+    pub lesson_id: usize,
     pub classroom_id: u8,
-    pub teacher_id: u8,
 }
 
 pub struct IllegalBuffer {
@@ -21,6 +26,12 @@ pub struct IllegalBuffer {
     can_hold: HashSet<CanHold>,
 
     illegal_states: Vec<IllegalState>,
+    // Jednostki czasu objęte zakazami dni i godzin, osobno dla każdej grupy,
+    // nauczyciela i sali
+    blocked_times: HashMap<IllegalStateSubject, Vec<bool>>,
+    // Grupy i długość lekcji (indeksowane id lekcji)
+    lesson_shapes: Vec<LessonShape>,
+    slots: SlotTable,
 }
 
 impl IllegalBuffer {
@@ -28,11 +39,26 @@ impl IllegalBuffer {
         can_teach: HashSet<CanTeach>,
         can_hold: HashSet<CanHold>,
         illegal_states: Vec<IllegalState>,
+        lesson_shapes: Vec<LessonShape>,
+        slots: SlotTable,
     ) -> Self {
+        let mut blocked_times = HashMap::new();
+        for illegal_state in &illegal_states {
+            for time in 0..slots.len() as u16 {
+                if illegal_state.object.covers_time(time, &slots) == Some(true) {
+                    blocked_times
+                        .entry(illegal_state.subject.clone())
+                        .or_insert_with(|| vec![false; slots.len()])[time as usize] = true;
+                }
+            }
+        }
         Self {
             can_teach,
             can_hold,
             illegal_states,
+            blocked_times,
+            lesson_shapes,
+            slots,
         }
     }
 
@@ -44,15 +70,223 @@ impl IllegalBuffer {
         self.can_hold.contains(&can_hold)
     }
 
-    pub fn insert_can_teach(&mut self, can_teach: CanTeach) -> bool {
-        self.can_teach.insert(can_teach)
+    pub fn is_illegal(&self, lesson_id: usize, lesson: Lesson) -> bool {
+        let can_teach = CanTeach {
+            lesson_id,
+            teacher_id: lesson.teacher,
+        };
+        let can_hold = CanHold {
+            lesson_id,
+            classroom_id: lesson.classroom,
+        };
+        let shape = &self.lesson_shapes[lesson_id];
+        crosses_day_boundary(lesson, shape, &self.slots)
+            || !self.can_teach(can_teach)
+            || !self.can_hold(can_hold)
+            || self
+                .illegal_states
+                .iter()
+                .any(|illegal_state| illegal_state.is_violated_by(lesson, shape, &self.slots))
     }
 
-    pub fn insert_can_hold(&mut self, can_hold: CanHold) -> bool {
-        self.can_hold.insert(can_hold)
+    // Szacuje liczbę dozwolonych stanów lekcji spośród podanych terminów, nauczycieli
+    // i sal bez sprawdzania wszystkich ich kombinacji. Zakazy dni i godzin są brane
+    // z masek jednostek czasu, a pozostałe są sprawdzane dla par nauczyciel-sala
+    // i przenoszone proporcjonalnie na każdy termin.
+    pub fn count_legal_states(
+        &self,
+        lesson_id: usize,
+        times: &[u16],
+        teachers: &[u8],
+        classrooms: &[u8],
+    ) -> usize {
+        let shape = &self.lesson_shapes[lesson_id];
+        let is_free =
+            |subject: IllegalStateSubject, time: u16| match self.blocked_times.get(&subject) {
+                Some(blocked) => !blocked[time as usize..time as usize + shape.duration as usize]
+                    .iter()
+                    .any(|&x| x),
+                None => true,
+            };
+
+        let teachers = teachers
+            .iter()
+            .copied()
+            .filter(|&teacher_id| {
+                self.can_teach(CanTeach {
+                    lesson_id,
+                    teacher_id,
+                })
+            })
+            .collect::<Vec<_>>();
+        let classrooms = classrooms
+            .iter()
+            .copied()
+            .filter(|&classroom_id| {
+                self.can_hold(CanHold {
+                    lesson_id,
+                    classroom_id,
+                })
+            })
+            .collect::<Vec<_>>();
+        let all_pairs = teachers.len() * classrooms.len();
+        if all_pairs == 0 {
+            return 0;
+        }
+
+        // Zakazy niezależne od terminu nie sprawdzają czasu lekcji
+        let timeless = self
+            .illegal_states
+            .iter()
+            .filter(|x| x.object.covers_time(0, &self.slots).is_none())
+            .collect::<Vec<_>>();
+        let legal_pairs = teachers
+            .iter()
+            .flat_map(|&teacher| {
+                classrooms
+                    .iter()
+                    .map(move |&classroom| (teacher, classroom))
+            })
+            .filter(|&(teacher, classroom)| {
+                let lesson = Lesson {
+                    time: 0,
+                    teacher,
+                    classroom,
+                };
+                !timeless
+                    .iter()
+                    .any(|x| x.is_violated_by(lesson, shape, &self.slots))
+            })
+            .count();
+
+        let free_pairs = times
+            .iter()
+            .filter(|&&time| {
+                let lesson = Lesson {
+                    time,
+                    teacher: 0,
+                    classroom: 0,
+                };
+                !crosses_day_boundary(lesson, shape, &self.slots)
+                    && shape
+                        .groups
+                        .iter()
+                        .all(|&group| is_free(IllegalStateSubject::StudentGroup(group), time))
+            })
+            .map(|&time| {
+                let free_teachers = teachers
+                    .iter()
+                    .filter(|&&x| is_free(IllegalStateSubject::Teacher(x), time))
+                    .count();
+                let free_classrooms = classrooms
+                    .iter()
+                    .filter(|&&x| is_free(IllegalStateSubject::Classroom(x), time))
+                    .count();
+                free_teachers * free_classrooms
+            })
+            .sum::<usize>();
+        free_pairs * legal_pairs / all_pairs
     }
+}
+
+#[cfg(test)]
+impl IllegalBuffer {
+    // Bufor do testów: każdą lekcję może prowadzić każdy z pierwszych `teachers`
+    // nauczycieli w każdej z pierwszych `classrooms` sal
+    pub fn permissive(
+        lesson_shapes: &[LessonShape],
+        slots: &SlotTable,
+        teachers: u8,
+        classrooms: u8,
+    ) -> Self {
+        let lessons = 0..lesson_shapes.len();
+        Self::new(
+            iproduct!(lessons.clone(), 0..teachers)
+                .map(|(lesson_id, teacher_id)| CanTeach {
+                    lesson_id,
+                    teacher_id,
+                })
+                .collect(),
+            iproduct!(lessons, 0..classrooms)
+                .map(|(lesson_id, classroom_id)| CanHold {
+                    lesson_id,
+                    classroom_id,
+                })
+                .collect(),
+            Vec::new(),
+            lesson_shapes.to_vec(),
+            slots.clone(),
+        )
+    }
+
+    pub fn with_illegal_states(self, illegal_states: Vec<IllegalState>) -> Self {
+        Self::new(
+            self.can_teach,
+            self.can_hold,
+            illegal_states,
+            self.lesson_shapes,
+            self.slots,
+        )
+    }
+}
+
+// Lekcja trwająca kilka jednostek czasu musi się zmieścić w jednym dniu
+fn crosses_day_boundary(lesson: Lesson, shape: &LessonShape, slots: &SlotTable) -> bool {
+    let last_time = lesson.time as usize + shape.duration as usize - 1;
+    last_time >= slots.len() || slots.day(lesson.time) != slots.day(last_time as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::illegal_state::IllegalStateObject;
+
+    #[test]
+    fn legal_states_are_counted_from_masks() {
+        let slots = SlotTable::new(&Default::default(), 2, 1).unwrap();
+        let shapes = vec![LessonShape::new(vec![0], 2, 0)];
+        // Nauczyciel 1 nie prowadzi zajęć w pierwszym dniu, a grupa 0 w drugim
+        // dniu o 12:00
+        let illegal_buffer =
+            IllegalBuffer::permissive(&shapes, &slots, 2, 2).with_illegal_states(vec![
+                IllegalState {
+                    subject: IllegalStateSubject::Teacher(1),
+                    object: IllegalStateObject::Day(0),
+                },
+                IllegalState {
+                    subject: IllegalStateSubject::StudentGroup(0),
+                    object: IllegalStateObject::DayHour(crate::data_types::SimpleDate::new(
+                        1, 12, 0,
+                    )),
+                },
+            ]);
+        let times = (0..slots.len() as u16).collect::<Vec<_>>();
+        let exact = iproduct!(times.iter(), 0..2, 0..3)
+            .filter(|&(&time, teacher, classroom)| {
+                !illegal_buffer.is_illegal(
+                    0,
+                    Lesson {
+                        time,
+                        teacher,
+                        classroom,
+                    },
+                )
+            })
+            .count();
+        assert_eq!(exact, 22);
+        assert_eq!(
+            illegal_buffer.count_legal_states(0, &times, &[0, 1], &[0, 1, 2]),
+            exact
+        );
 
-    pub fn is_illegal(&self, lesson: Lesson) -> bool {
-        false
+        // Nauczyciel 0 nie może prowadzić zajęć w sali 1
+        let illegal_buffer = illegal_buffer.with_illegal_states(vec![IllegalState {
+            subject: IllegalStateSubject::Teacher(0),
+            object: IllegalStateObject::Classroom(1),
+        }]);
+        assert_eq!(
+            illegal_buffer.count_legal_states(0, &times, &[0, 1], &[0, 1, 2]),
+            30
+        );
     }
 }
@@ -8,27 +8,28 @@ use super::{
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 pub struct ClassroomTimeKey {
     pub classroom: u8,
-    pub time: u8,
+    pub time: u16,
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 pub struct TeacherTimeKey {
     pub teacher: u8,
-    pub time: u8,
+    pub time: u16,
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 pub struct GroupTimeKey {
     pub group: u8,
-    pub time: u8,
+    pub time: u16,
 }
 
+// Grupy biorące udział w lekcji nie zmieniają się w trakcie wyżarzania, więc nie są
+// częścią `Lesson`, tylko są przechowywane w `InnerState::lesson_groups`.
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Lesson {
-    pub time: u8,
+    pub time: u16,
     pub teacher: u8,
     pub classroom: u8,
-    pub group: u8,
 }
 
 impl Lesson {
@@ -46,9 +47,9 @@ impl Lesson {
         }
     }
 
-    pub fn group_time_key(&self) -> GroupTimeKey {
+    pub fn group_time_key(&self, group: u8) -> GroupTimeKey {
         GroupTimeKey {
-            group: self.group,
+            group,
             time: self.time,
         }
     }
@@ -61,14 +62,39 @@ impl Lesson {
         Lesson { classroom, ..*self }
     }
 
-    pub fn with_time(&self, time: u8) -> Lesson {
+    pub fn with_time(&self, time: u16) -> Lesson {
         Lesson { time, ..*self }
     }
 }
 
+// Cechy lekcji, które nie zmieniają się w trakcie wyżarzania
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LessonShape {
+    pub groups: Vec<u8>,
+    pub duration: u8, // Liczba kolejnych jednostek czasu zajmowanych przez lekcję
+    // Lekcje tego samego przedmiotu tych samych grup mają ten sam kurs
+    pub course: usize,
+}
+
+impl LessonShape {
+    pub fn new(groups: Vec<u8>, duration: u8, course: usize) -> Self {
+        Self {
+            groups,
+            duration,
+            course,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct InnerState {
     lessons: Vec<Lesson>,
+    lesson_shapes: Vec<LessonShape>,
+    // Lekcje, których nie udało się umieścić w planie, nie występują w mapach
+    // i nie są zmieniane przez mutacje
+    placed: Vec<bool>,
+    // Lekcje o terminie ustalonym z góry, których mutacje nie przesuwają
+    pinned_time: Vec<bool>,
 
     teacher_time: HashMap<TeacherTimeKey, usize>,
     classroom_time: HashMap<ClassroomTimeKey, usize>,
@@ -78,77 +104,106 @@ pub struct InnerState {
 #[derive(Debug)]
 pub struct InnerStateRef<'a> {
     pub lessons: &'a Vec<Lesson>,
-
-    pub teacher_time: &'a HashMap<TeacherTimeKey, usize>,
-    pub classroom_time: &'a HashMap<ClassroomTimeKey, usize>,
-    pub group_time: &'a HashMap<GroupTimeKey, usize>,
+    pub lesson_shapes: &'a Vec<LessonShape>,
+    pub placed: &'a Vec<bool>,
+    pub pinned_time: &'a Vec<bool>,
 }
 
 impl InnerState {
-    pub fn new(lesson_count: usize) -> Self {
+    pub fn new(lesson_shapes: Vec<LessonShape>) -> Self {
         Self {
-            lessons: vec![Default::default(); lesson_count],
+            lessons: vec![Default::default(); lesson_shapes.len()],
+            placed: vec![false; lesson_shapes.len()],
+            pinned_time: vec![false; lesson_shapes.len()],
+            lesson_shapes,
             ..Default::default()
         }
     }
 
-    pub fn state_ref(&self) -> InnerStateRef {
+    pub fn state_ref(&self) -> InnerStateRef<'_> {
         InnerStateRef {
             lessons: &self.lessons,
-            teacher_time: &self.teacher_time,
-            classroom_time: &self.classroom_time,
-            group_time: &self.group_time,
+            lesson_shapes: &self.lesson_shapes,
+            placed: &self.placed,
+            pinned_time: &self.pinned_time,
         }
     }
 
-    fn collision_checks(&self, lesson: Lesson) -> [Option<&usize>; 3] {
-        let group_collision = self.group_time.get(&lesson.group_time_key());
-        let teacher_collision = self.teacher_time.get(&lesson.teacher_time_key());
-        let classroom_collision = self.classroom_time.get(&lesson.classroom_time_key());
-        [group_collision, teacher_collision, classroom_collision]
+    // Jednostki czasu zajmowane przez lekcję trwającą kilka jednostek
+    fn lesson_parts(&self, lesson: Lesson, lesson_id: usize) -> impl Iterator<Item = Lesson> {
+        let duration = self.lesson_shapes[lesson_id].duration;
+        (lesson.time..lesson.time.saturating_add(duration as u16))
+            .map(move |time| lesson.with_time(time))
     }
 
-    fn all_no_collision(&self, lesson: Lesson) -> bool {
-        self.collision_checks(lesson) == [None; 3]
+    // Lekcja wspólna dla kilku grup zajmuje termin każdej z nich
+    fn collision_checks(
+        &self,
+        lesson: Lesson,
+        lesson_id: usize,
+    ) -> impl Iterator<Item = Option<&usize>> {
+        let groups = &self.lesson_shapes[lesson_id].groups;
+        self.lesson_parts(lesson, lesson_id).flat_map(move |part| {
+            let teacher_collision = self.teacher_time.get(&part.teacher_time_key());
+            let classroom_collision = self.classroom_time.get(&part.classroom_time_key());
+            let group_collisions = groups
+                .iter()
+                .map(move |&group| self.group_time.get(&part.group_time_key(group)));
+            std::iter::once(teacher_collision)
+                .chain(std::iter::once(classroom_collision))
+                .chain(group_collisions)
+        })
+    }
+
+    fn all_no_collision(&self, lesson: Lesson, lesson_id: usize) -> bool {
+        self.collision_checks(lesson, lesson_id)
+            .all(|x| x.is_none())
     }
 
     pub fn check_collision(&self, lesson: Lesson, lesson_id: usize) -> InnerCollision {
         let mut result = InnerCollision::NoCollisions;
-        for col_option in &self.collision_checks(lesson) {
-            if let Some(col) = *col_option {
-                match result {
-                    InnerCollision::NoCollisions => {
-                        // Lekcja nie koliduje sama z sobą.
-                        if *col != lesson_id {
-                            result = InnerCollision::CollidesWithOne(*col);
-                        }
+        for col in self.collision_checks(lesson, lesson_id).flatten() {
+            match result {
+                InnerCollision::NoCollisions => {
+                    // Lekcja nie koliduje sama z sobą.
+                    if *col != lesson_id {
+                        result = InnerCollision::CollidesWithOne(*col);
                     }
-                    InnerCollision::CollidesWithOne(previous_collision) => {
-                        // Podwójna kolizja z tą samą lekcją nie powoduje problemów.
-                        // Lekcja nie koliduje sama z sobą.
-                        if previous_collision != *col && *col != lesson_id {
-                            result = InnerCollision::TooComplex;
-                        }
+                }
+                InnerCollision::CollidesWithOne(previous_collision) => {
+                    // Podwójna kolizja z tą samą lekcją nie powoduje problemów.
+                    // Lekcja nie koliduje sama z sobą.
+                    if previous_collision != *col && *col != lesson_id {
+                        result = InnerCollision::TooComplex;
                     }
-                    InnerCollision::TooComplex => {}
                 }
+                InnerCollision::TooComplex => {}
             }
         }
         result
     }
 
     fn put_lesson(&mut self, lesson: Lesson, lesson_id: usize) {
-        let insert_results = [
-            self.classroom_time
-                .insert(lesson.classroom_time_key(), lesson_id),
-            self.teacher_time
-                .insert(lesson.teacher_time_key(), lesson_id),
-            self.group_time.insert(lesson.group_time_key(), lesson_id),
-        ];
+        let duration = self.lesson_shapes[lesson_id].duration;
+        for time in lesson.time..lesson.time.saturating_add(duration as u16) {
+            let part = lesson.with_time(time);
+            let insert_results = [
+                self.classroom_time
+                    .insert(part.classroom_time_key(), lesson_id),
+                self.teacher_time.insert(part.teacher_time_key(), lesson_id),
+            ];
+            assert_eq!(insert_results, [None; 2]);
+
+            for &group in &self.lesson_shapes[lesson_id].groups {
+                let insert_result = self
+                    .group_time
+                    .insert(part.group_time_key(group), lesson_id);
+                assert_eq!(insert_result, None);
+            }
+        }
 
         self.lessons[lesson_id] = lesson;
-
-        assert_eq!(insert_results, [None; 3]);
+        self.placed[lesson_id] = true;
     }
 
     // Używane spoza tego modułu przez AnnealingAdapter
@@ -157,8 +212,7 @@ impl InnerState {
         lesson_id: usize,
         teacher: u8,
         classroom: u8,
-        time: u8,
-        group: u8,
+        time: u16,
     ) -> bool {
         assert!(
             self.lessons.len() > lesson_id,
@@ -170,11 +224,10 @@ impl InnerState {
         let lesson = Lesson {
             teacher,
             time,
-            group,
             classroom,
         };
 
-        if !self.all_no_collision(lesson) {
+        if !self.all_no_collision(lesson, lesson_id) {
             return false;
         }
 
@@ -182,11 +235,83 @@ impl InnerState {
         true
     }
 
+    // Usuwa lekcję z planu, używane przy budowie planu początkowego
+    pub fn unplace_lesson(&mut self, lesson_id: usize) -> Lesson {
+        self.remove_lesson(lesson_id);
+        self.lessons[lesson_id]
+    }
+
+    // Przywraca plan do stanu zapisanego wcześniej z `state_ref().lessons`
+    // i `state_ref().placed`
+    pub fn restore_lessons(&mut self, lessons: &[Lesson], placed: &[bool]) {
+        for lesson_id in 0..self.lessons.len() {
+            if self.placed[lesson_id] {
+                self.remove_lesson(lesson_id);
+            }
+        }
+        for lesson_id in 0..self.lessons.len() {
+            self.lessons[lesson_id] = lessons[lesson_id];
+            if placed[lesson_id] {
+                self.put_lesson(lessons[lesson_id], lesson_id);
+            }
+        }
+    }
+
+    // Termin przypiętej lekcji jest zapamiętany także wtedy, gdy nie udało się
+    // jej umieścić w planie
+    pub fn pin_time(&mut self, lesson_id: usize, time: u16) {
+        self.pinned_time[lesson_id] = true;
+        self.lessons[lesson_id].time = time;
+    }
+
+    // Umieszcza w planie lekcję spoza planu. Jeśli nowy stan koliduje z jedną
+    // lekcją o nieprzypiętym terminie, tamta jest usuwana z planu. Zwraca None,
+    // jeśli lekcji nie da się tak umieścić.
+    pub fn insert_lesson(
+        &mut self,
+        lesson_id: usize,
+        lesson: Lesson,
+        illegal_buffer: &IllegalBuffer,
+    ) -> Option<ChangedLessons> {
+        assert!(
+            !self.placed[lesson_id],
+            "Inserting placed lesson {}",
+            lesson_id
+        );
+        if illegal_buffer.is_illegal(lesson_id, lesson) {
+            return None;
+        }
+        match self.check_collision(lesson, lesson_id) {
+            InnerCollision::NoCollisions => {
+                self.put_lesson(lesson, lesson_id);
+                Some(ChangedLessons::single(lesson_id))
+            }
+            InnerCollision::CollidesWithOne(ejected) if !self.pinned_time[ejected] => {
+                self.remove_lesson(ejected);
+                self.put_lesson(lesson, lesson_id);
+                Some(ChangedLessons::swap(lesson_id, ejected))
+            }
+            _ => None,
+        }
+    }
+
+    // Cofa `insert_lesson`, usunięta lekcja wraca do poprzedniego stanu
+    pub fn revert_insertion(&mut self, changed: ChangedLessons) -> ChangedLessons {
+        self.remove_lesson(changed.target);
+        if let Some(ejected) = changed.swapped {
+            self.put_lesson(self.lessons[ejected], ejected);
+        }
+        changed
+    }
+
     pub fn assert_maps_synchronized(&self, msg: &str) {
         for (lesson_id, lesson) in self.lessons.iter().enumerate() {
-            assert_eq!(
-                self.collision_checks(*lesson),
-                [Some(&lesson_id); 3],
+            if !self.placed[lesson_id] {
+                continue;
+            }
+            assert!(
+                self.collision_checks(*lesson, lesson_id)
+                    .all(|x| x == Some(&lesson_id)),
                 "Maps not synchronized ({})\n{:?}",
                 msg,
                 self.state_ref()
@@ -196,40 +321,74 @@ impl InnerState {
 
     fn remove_lesson(&mut self, lesson_id: usize) {
         let lesson = self.lessons[lesson_id];
-
-        let removed = [
-            self.classroom_time.remove(&lesson.classroom_time_key()),
-            self.teacher_time.remove(&lesson.teacher_time_key()),
-            self.group_time.remove(&lesson.group_time_key()),
-        ];
-
-        assert_eq!(
-            removed,
-            [Some(lesson_id); 3],
-            "Unexpected lesson when removing, expected {}",
+        let duration = self.lesson_shapes[lesson_id].duration;
+        assert!(
+            self.placed[lesson_id],
+            "Removing unplaced lesson {}",
             lesson_id
         );
+        self.placed[lesson_id] = false;
+
+        for time in lesson.time..lesson.time.saturating_add(duration as u16) {
+            let part = lesson.with_time(time);
+            let removed = [
+                self.classroom_time.remove(&part.classroom_time_key()),
+                self.teacher_time.remove(&part.teacher_time_key()),
+            ];
+            assert_eq!(
+                removed,
+                [Some(lesson_id); 2],
+                "Unexpected lesson when removing, expected {}",
+                lesson_id
+            );
+
+            for &group in &self.lesson_shapes[lesson_id].groups {
+                let removed = self.group_time.remove(&part.group_time_key(group));
+                assert_eq!(
+                    removed,
+                    Some(lesson_id),
+                    "Unexpected lesson when removing, expected {}",
+                    lesson_id
+                );
+            }
+        }
     }
 
+    // Lekcje o różnej długości mogą po zamianie kolidować ze sobą albo z innymi
+    // lekcjami, więc nowe stany są sprawdzane po usunięciu obu lekcji. Jeśli
+    // zamiana się nie uda, stan pozostaje bez zmian.
     fn replace_lessons(
         &mut self,
         left_id: usize,
         left_new_state: Lesson,
         right_id: usize,
         right_new_state: Lesson,
-    ) {
+    ) -> bool {
+        let left_old_state = self.lessons[left_id];
+        let right_old_state = self.lessons[right_id];
+
         self.remove_lesson(left_id);
         self.remove_lesson(right_id);
 
-        self.put_lesson(left_new_state, left_id);
-        self.put_lesson(right_new_state, right_id);
+        if self.all_no_collision(left_new_state, left_id) {
+            self.put_lesson(left_new_state, left_id);
+            if self.all_no_collision(right_new_state, right_id) {
+                self.put_lesson(right_new_state, right_id);
+                return true;
+            }
+            self.remove_lesson(left_id);
+        }
+
+        self.put_lesson(left_old_state, left_id);
+        self.put_lesson(right_old_state, right_id);
+        false
     }
 
     fn apply_non_time_mutation(
         &mut self,
         mutation: Mutation,
         illegal_buffer: Option<&IllegalBuffer>,
-    ) -> bool {
+    ) -> Option<ChangedLessons> {
         let target_lesson = mutation.target_lesson;
         let lesson = self.lessons[target_lesson];
         let changed_lesson = match mutation.mutation_type {
@@ -238,12 +397,17 @@ impl InnerState {
             _ => unreachable!(),
         };
 
+        if is_illegal(illegal_buffer, target_lesson, changed_lesson) {
+            return None;
+        }
+
         let collision = self.check_collision(changed_lesson, mutation.target_lesson);
 
         match collision {
             InnerCollision::NoCollisions => {
                 self.remove_lesson(target_lesson);
                 self.put_lesson(changed_lesson, target_lesson);
+                Some(ChangedLessons::single(target_lesson))
             }
             InnerCollision::CollidesWithOne(collision_id) => {
                 let collision_old_state = self.lessons[collision_id];
@@ -256,24 +420,30 @@ impl InnerState {
                     }
                     _ => unreachable!(),
                 };
+                if is_illegal(illegal_buffer, collision_id, collision_new_state) {
+                    return None;
+                }
                 self.replace_lessons(
                     target_lesson,
                     changed_lesson,
                     collision_id,
                     collision_new_state,
-                );
+                )
+                .then(|| ChangedLessons::swap(target_lesson, collision_id))
             }
-            InnerCollision::TooComplex => return false,
+            InnerCollision::TooComplex => None,
         }
-        true
     }
 
     fn apply_time_mutation(
         &mut self,
         mutation: Mutation,
         illegal_buffer: Option<&IllegalBuffer>,
-    ) -> bool {
+    ) -> Option<ChangedLessons> {
         let target_lesson = mutation.target_lesson;
+        if self.pinned_time[target_lesson] {
+            return None;
+        }
         let lesson_old_state = self.lessons[target_lesson];
         let new_time = match mutation.mutation_type {
             MutationType::ChangeTime(time) => time,
@@ -282,46 +452,60 @@ impl InnerState {
 
         let lesson_new_state = lesson_old_state.with_time(new_time);
 
+        if is_illegal(illegal_buffer, target_lesson, lesson_new_state) {
+            return None;
+        }
+
         let collision = self.check_collision(lesson_new_state, target_lesson);
 
         match collision {
             InnerCollision::NoCollisions => {
                 self.remove_lesson(target_lesson);
                 self.put_lesson(lesson_new_state, target_lesson);
+                Some(ChangedLessons::single(target_lesson))
             }
             InnerCollision::CollidesWithOne(collision_id) => {
+                // Lekcja, z którą nastąpiła kolizja, jest przesuwana o tyle samo
+                // w przeciwną stronę. Dla lekcji o długości jednej jednostki
+                // oznacza to zamianę terminów, a mutacja odwrotna zawsze
+                // przywraca poprzedni stan. Przypiętej lekcji nie można przesunąć.
+                if self.pinned_time[collision_id] {
+                    return None;
+                }
                 let collision_old_state = self.lessons[collision_id];
-                let collision_new_state = collision_old_state.with_time(lesson_old_state.time);
-
-                let recursive_collision = self.check_collision(collision_new_state, collision_id);
-                if let InnerCollision::TooComplex = recursive_collision {
-                    return false;
+                let collision_new_time = collision_old_state.time as i32
+                    + lesson_old_state.time as i32
+                    - new_time as i32;
+                if !(0..=u16::MAX as i32).contains(&collision_new_time) {
+                    return None;
                 }
+                let collision_new_state = collision_old_state.with_time(collision_new_time as u16);
 
-                // Kolizja rekurencyjna musi wynosić CollidesWithOne(target_lesson) albo TooComplex.
-                // Jeśli kolizja jest równa NoCollisions, jest to błąd programu.
-                assert_eq!(
-                    recursive_collision,
-                    InnerCollision::CollidesWithOne(target_lesson),
-                    "Recursive collision is not with the original lesson"
-                );
-
-                self.remove_lesson(target_lesson);
-                self.remove_lesson(collision_id);
+                if is_illegal(illegal_buffer, collision_id, collision_new_state) {
+                    return None;
+                }
 
-                self.put_lesson(lesson_new_state, target_lesson);
-                self.put_lesson(collision_new_state, collision_id);
+                self.replace_lessons(
+                    target_lesson,
+                    lesson_new_state,
+                    collision_id,
+                    collision_new_state,
+                )
+                .then(|| ChangedLessons::swap(target_lesson, collision_id))
             }
-            InnerCollision::TooComplex => return false,
-        };
-        true
+            InnerCollision::TooComplex => None,
+        }
     }
 
+    // Zwraca None, jeśli mutacja nie została wykonana
     pub fn apply_mutation(
         &mut self,
         mutation: Mutation,
         illegal_buffer: Option<&IllegalBuffer>,
-    ) -> bool {
+    ) -> Option<ChangedLessons> {
+        if !self.placed[mutation.target_lesson] {
+            return None;
+        }
         match &mutation.mutation_type {
             &MutationType::ChangeTime(_) => self.apply_time_mutation(mutation, illegal_buffer),
             _ => self.apply_non_time_mutation(mutation, illegal_buffer),
@@ -329,6 +513,39 @@ impl InnerState {
     }
 }
 
+// Bez bufora niedozwolonych stanów (np. przy cofaniu mutacji) każdy stan jest dozwolony
+fn is_illegal(illegal_buffer: Option<&IllegalBuffer>, lesson_id: usize, lesson: Lesson) -> bool {
+    illegal_buffer.is_some_and(|x| x.is_illegal(lesson_id, lesson))
+}
+
+// Lekcje zmienione przez mutację: jej cel i ewentualnie lekcja, z którą nastąpiła zamiana
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ChangedLessons {
+    pub target: usize,
+    // Lekcja zamieniona z `target` albo usunięta z planu przy wstawianiu
+    pub swapped: Option<usize>,
+}
+
+impl ChangedLessons {
+    fn single(target: usize) -> Self {
+        Self {
+            target,
+            swapped: None,
+        }
+    }
+
+    fn swap(target: usize, swapped: usize) -> Self {
+        Self {
+            target,
+            swapped: Some(swapped),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        std::iter::once(self.target).chain(self.swapped)
+    }
+}
+
 #[derive(PartialEq, Eq, Debug)]
 pub enum InnerCollision {
     NoCollisions,
@@ -336,16 +553,6 @@ pub enum InnerCollision {
     TooComplex,
 }
 
-impl InnerCollision {
-    pub fn is_no_collisions(&self) -> bool {
-        if let Self::NoCollisions = self {
-            true
-        } else {
-            false
-        }
-    }
-}
-
 impl std::fmt::Debug for InnerState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("InnerState")
@@ -361,37 +568,212 @@ impl std::fmt::Debug for InnerState {
 mod test {
     use super::*;
 
+    #[test]
+    fn pinned_lesson_is_never_moved() {
+        let mut inner_state = InnerState::new(vec![LessonShape::new(vec![0], 1, 0); 2]);
+        assert!(inner_state.place_lesson(0, 0, 0, 0));
+        assert!(inner_state.place_lesson(1, 0, 0, 1));
+        inner_state.pin_time(0, 0);
+
+        let own = Mutation::new(0, MutationType::ChangeTime(2));
+        assert!(inner_state.apply_mutation(own, None).is_none());
+        // Zamiana przesunęłaby przypiętą lekcję
+        let swap = Mutation::new(1, MutationType::ChangeTime(0));
+        assert!(inner_state.apply_mutation(swap, None).is_none());
+        assert_eq!(inner_state.state_ref().lessons[0].time, 0);
+
+        let teacher = Mutation::new(0, MutationType::ChangeTeacher(1));
+        assert!(inner_state.apply_mutation(teacher, None).is_some());
+    }
+
+    // Dwa dni, w których każdą lekcję może prowadzić każdy z dwóch nauczycieli w sali 0
+    fn illegal_buffer(shapes: &[LessonShape]) -> IllegalBuffer {
+        let slots = crate::data_types::SlotTable::new(&Default::default(), 2, 1).unwrap();
+        IllegalBuffer::permissive(shapes, &slots, 2, 1)
+    }
+
+    #[test]
+    fn swap_into_illegal_state_is_rejected() {
+        use crate::illegal_state::{IllegalState, IllegalStateObject, IllegalStateSubject};
+
+        let shapes = vec![LessonShape::new(vec![0], 1, 0); 2];
+        // Nauczyciel 1 nie może prowadzić zajęć w pierwszym dniu
+        let illegal_buffer = illegal_buffer(&shapes).with_illegal_states(vec![IllegalState {
+            subject: IllegalStateSubject::Teacher(1),
+            object: IllegalStateObject::Day(0),
+        }]);
+        let mut inner_state = InnerState::new(shapes);
+        assert!(inner_state.place_lesson(0, 0, 0, 0));
+        assert!(inner_state.place_lesson(1, 1, 0, 6));
+
+        // Lekcja 0 może się odbyć w drugim dniu, ale zamieniona z nią lekcja 1
+        // trafiłaby do pierwszego
+        let mutation = Mutation::new(0, MutationType::ChangeTime(6));
+        assert!(!illegal_buffer.is_illegal(0, inner_state.lessons[0].with_time(6)));
+        assert!(inner_state
+            .apply_mutation(mutation, Some(&illegal_buffer))
+            .is_none());
+        inner_state.assert_maps_synchronized("swap_into_illegal_state_is_rejected");
+        assert_eq!(inner_state.state_ref().lessons[0].time, 0);
+        assert_eq!(inner_state.state_ref().lessons[1].time, 6);
+
+        assert!(inner_state.apply_mutation(mutation, None).is_some());
+        assert_eq!(inner_state.state_ref().lessons[1].time, 0);
+    }
+
+    #[test]
+    fn insertion_ejects_one_lesson_and_can_be_reverted() {
+        let shapes = vec![LessonShape::new(vec![0], 1, 0); 3];
+        let illegal_buffer = illegal_buffer(&shapes);
+        let mut inner_state = InnerState::new(shapes);
+        assert!(inner_state.place_lesson(0, 0, 0, 0));
+        assert!(inner_state.place_lesson(1, 1, 0, 1));
+        inner_state.pin_time(1, 1);
+        let lesson = |time| Lesson {
+            time,
+            teacher: 0,
+            classroom: 0,
+        };
+
+        // Lekcja o przypiętym terminie nie jest usuwana z planu
+        assert!(inner_state
+            .insert_lesson(2, lesson(1), &illegal_buffer)
+            .is_none());
+
+        let changed = inner_state
+            .insert_lesson(2, lesson(0), &illegal_buffer)
+            .unwrap();
+        assert_eq!(changed.iter().collect::<Vec<_>>(), vec![2, 0]);
+        assert_eq!(inner_state.state_ref().placed, &[false, true, true]);
+        inner_state.assert_maps_synchronized("After insertion");
+
+        inner_state.revert_insertion(changed);
+        assert_eq!(inner_state.state_ref().placed, &[true, true, false]);
+        assert_eq!(inner_state.state_ref().lessons[0], lesson(0));
+        inner_state.assert_maps_synchronized("After reverted insertion");
+    }
+
+    #[test]
+    fn restored_lessons_return_to_saved_state() {
+        let mut inner_state = InnerState::new(vec![LessonShape::new(vec![0], 1, 0); 2]);
+        assert!(inner_state.place_lesson(0, 0, 0, 0));
+        assert!(inner_state.place_lesson(1, 0, 0, 1));
+        let saved = inner_state.state_ref().lessons.clone();
+        let placed = inner_state.state_ref().placed.clone();
+
+        // Zamiana terminów obu lekcji
+        let mutation = Mutation::new(0, MutationType::ChangeTime(1));
+        assert!(inner_state.apply_mutation(mutation, None).is_some());
+        inner_state.restore_lessons(&saved, &placed);
+
+        assert_eq!(inner_state.state_ref().lessons, &saved);
+        inner_state.assert_maps_synchronized("After restore");
+    }
+
     #[test]
     fn time_mutation_works() {
-        let mut inner_state = InnerState::new(2);
-        assert_eq!(inner_state.place_lesson(0, 0, 0, 0, 0), true);
-        assert_eq!(inner_state.place_lesson(1, 0, 0, 0, 0), false);
-        assert_eq!(inner_state.place_lesson(1, 0, 0, 1, 0), true);
+        let mut inner_state = InnerState::new(vec![LessonShape::new(vec![0], 1, 0); 2]);
+        assert!(inner_state.place_lesson(0, 0, 0, 0));
+        assert!(!inner_state.place_lesson(1, 0, 0, 0));
+        assert!(inner_state.place_lesson(1, 0, 0, 1));
 
         let mutation = Mutation::new(0, MutationType::ChangeTime(1));
         let rev_mutation = mutation.reverse_mutation(inner_state.lessons[0]);
 
-        assert_eq!(inner_state.apply_mutation(mutation, None), true);
+        assert!(inner_state.apply_mutation(mutation, None).is_some());
 
         assert_eq!(inner_state.state_ref().lessons[0].time, 1);
         assert_eq!(inner_state.state_ref().lessons[1].time, 0);
 
-        assert_eq!(inner_state.apply_mutation(rev_mutation.get(), None), true);
+        assert!(inner_state
+            .apply_mutation(rev_mutation.get(), None)
+            .is_some());
 
         assert_eq!(inner_state.state_ref().lessons[0].time, 0);
         assert_eq!(inner_state.state_ref().lessons[1].time, 1);
     }
 
+    #[test]
+    fn shared_lesson_moves_with_all_groups() {
+        let mut inner_state = InnerState::new(vec![
+            LessonShape::new(vec![0, 1], 1, 0),
+            LessonShape::new(vec![1], 1, 0),
+            LessonShape::new(vec![0], 1, 0),
+        ]);
+        assert!(inner_state.place_lesson(0, 0, 0, 0));
+        assert!(!inner_state.place_lesson(1, 1, 1, 0));
+        assert!(inner_state.place_lesson(1, 1, 1, 1));
+        assert!(inner_state.place_lesson(2, 2, 2, 2));
+
+        let mutation = Mutation::new(0, MutationType::ChangeTime(1));
+        assert!(inner_state.apply_mutation(mutation, None).is_some());
+        inner_state.assert_maps_synchronized("shared_lesson_moves_with_all_groups");
+
+        assert_eq!(inner_state.state_ref().lessons[0].time, 1);
+        assert_eq!(inner_state.state_ref().lessons[1].time, 0);
+        assert_eq!(
+            inner_state
+                .group_time
+                .get(&GroupTimeKey { group: 0, time: 1 }),
+            Some(&0)
+        );
+        assert_eq!(
+            inner_state
+                .group_time
+                .get(&GroupTimeKey { group: 0, time: 0 }),
+            None
+        );
+    }
+
+    #[test]
+    fn multi_slot_lesson_moves_as_block() {
+        let mut inner_state = InnerState::new(vec![
+            LessonShape::new(vec![0], 2, 0),
+            LessonShape::new(vec![0], 2, 0),
+            LessonShape::new(vec![0], 1, 0),
+        ]);
+        assert!(inner_state.place_lesson(0, 0, 0, 0));
+        assert!(!inner_state.place_lesson(1, 1, 1, 1));
+        assert!(inner_state.place_lesson(1, 1, 1, 2));
+        assert!(inner_state.place_lesson(2, 2, 2, 4));
+
+        // Zamiana dwóch bloków o tej samej długości
+        let mutation = Mutation::new(0, MutationType::ChangeTime(2));
+        let rev_mutation = mutation.reverse_mutation(inner_state.lessons[0]);
+        assert!(inner_state.apply_mutation(mutation, None).is_some());
+        inner_state.assert_maps_synchronized("multi_slot_lesson_moves_as_block");
+        assert_eq!(inner_state.state_ref().lessons[0].time, 2);
+        assert_eq!(inner_state.state_ref().lessons[1].time, 0);
+
+        assert!(inner_state
+            .apply_mutation(rev_mutation.get(), None)
+            .is_some());
+        assert_eq!(inner_state.state_ref().lessons[0].time, 0);
+        assert_eq!(inner_state.state_ref().lessons[1].time, 2);
+
+        // Krótsza lekcja jest przesuwana o tyle samo w przeciwną stronę
+        let mutation = Mutation::new(1, MutationType::ChangeTime(4));
+        assert!(inner_state.apply_mutation(mutation, None).is_some());
+        assert_eq!(inner_state.state_ref().lessons[1].time, 4);
+        assert_eq!(inner_state.state_ref().lessons[2].time, 2);
+
+        // Po przesunięciu lekcje nachodziłyby na siebie
+        let mutation = Mutation::new(0, MutationType::ChangeTime(1));
+        assert!(inner_state.apply_mutation(mutation, None).is_none());
+        inner_state.assert_maps_synchronized("multi_slot_lesson_moves_as_block");
+        assert_eq!(inner_state.state_ref().lessons[0].time, 0);
+        assert_eq!(inner_state.state_ref().lessons[2].time, 2);
+    }
+
     #[test]
     #[should_panic]
     fn assert_synchronized_works() {
-        let mut inner_state = InnerState::new(10);
+        let mut inner_state = InnerState::new(vec![LessonShape::new(vec![0], 1, 0); 10]);
         inner_state.put_lesson(
             Lesson {
                 classroom: 0,
                 teacher: 0,
                 time: 0,
-                group: 0,
             },
             0,
         );
@@ -2,6 +2,8 @@ pub mod adapter;
 pub mod annealing_buffer;
 pub mod annealing_functions;
 pub mod annealing_state;
+pub mod construction;
+pub mod cooling;
 pub mod energy;
 pub mod illegal_buffer;
 pub mod inner_state;
@@ -2,17 +2,28 @@ use super::{
     annealing_buffer::{AnnealingBuffer, Lesson},
     illegal_buffer::IllegalBuffer,
 };
-use rand::random;
+use rand::Rng;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum MutationType {
     ChangeTeacher(u8),
-    ChangeTime(u8),
+    ChangeTime(u16),
     ChangeClassroom(u8),
 }
 
 use MutationType::*;
 
+impl MutationType {
+    pub fn applied_to(&self, lesson: Lesson) -> Lesson {
+        match *self {
+            ChangeTeacher(teacher) => lesson.with_teacher(teacher),
+            ChangeTime(time) => lesson.with_time(time),
+            ChangeClassroom(classroom) => lesson.with_classroom(classroom),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Mutation {
     pub target_lesson: usize,
@@ -40,37 +51,53 @@ impl Mutation {
         }
     }
 
-    pub fn legal_of_buffer(buffer: &AnnealingBuffer) -> Mutation {
+    pub fn legal_of_buffer(
+        buffer: &AnnealingBuffer,
+        illegal_buffer: &IllegalBuffer,
+        rng: &mut impl Rng,
+    ) -> Mutation {
         let mut target_lesson: usize;
         let mut mutation_type: MutationType;
         let state_ref = buffer.inner_state.state_ref();
 
         loop {
-            target_lesson = random::<usize>() % state_ref.lessons.len();
+            target_lesson = rng.gen::<usize>() % state_ref.lessons.len();
+            if !state_ref.placed[target_lesson] {
+                continue;
+            }
 
-            // random::<f32>() mieści się w przedziale [0, 1)
+            // rng.gen::<f32>() mieści się w przedziale [0, 1)
             // 50% szansy na zmianę terminu
             // 30% szansy na zmianę sali
             // 20% szansy na zmianę prowadzącego
-            mutation_type = match random::<f32>() {
-                r if r < 0.5 => ChangeTime(random::<u8>() % buffer.max_time),
-                r if r < 0.8 => ChangeClassroom(random::<u8>() % buffer.classroom_count),
-                _r => ChangeTeacher(random::<u8>() % buffer.classroom_count),
+            mutation_type = match rng.gen::<f32>() {
+                r if r < 0.5 => ChangeTime(rng.gen::<u16>() % buffer.max_time),
+                r if r < 0.8 => {
+                    let classrooms = &buffer.lesson_classrooms[target_lesson];
+                    ChangeClassroom(classrooms[rng.gen::<usize>() % classrooms.len()])
+                }
+                _r => {
+                    let teachers = &buffer.lesson_teachers[target_lesson];
+                    ChangeTeacher(teachers[rng.gen::<usize>() % teachers.len()])
+                }
             };
 
-            if false {
-                // TODO: sprawdzaj, czy wygenerowany stan znajduje się w zbiorze
-                // niedozwolonych
+            // Przypiętych terminów nie zmieniają też zamiany w InnerState
+            if matches!(mutation_type, ChangeTime(_)) && state_ref.pinned_time[target_lesson] {
+                continue;
+            }
+
+            // Stan lekcji, z którą nastąpi ewentualna zamiana, jest sprawdzany
+            // dopiero w InnerState::apply_mutation
+            let lesson = state_ref.lessons[target_lesson];
+            if illegal_buffer.is_illegal(target_lesson, mutation_type.applied_to(lesson)) {
                 continue;
             }
 
             break;
         }
 
-        Mutation {
-            target_lesson,
-            mutation_type,
-        }
+        Mutation::new(target_lesson, mutation_type)
     }
 
     // Stwórz mutację, której wykonanie przywróci stan do stanu przed wykonaniem
@@ -0,0 +1,129 @@
+use crate::data_types::{AnnealingSettings, CoolingKind};
+
+// Opcje wiersza poleceń. Dane wejściowe są zawsze czytane ze standardowego wejścia.
+#[derive(Debug, Default, PartialEq)]
+pub struct CliOptions {
+    // Poprzedni plan (output.json), od którego zaczyna się wyżarzanie
+    pub previous_plan: Option<String>,
+    // Zastępują ustawienia wyżarzania z danych wejściowych
+    pub iterations: Option<usize>,
+    pub cooling: Option<CoolingKind>,
+    pub initial_temperature: Option<f32>,
+    pub time_limit: Option<f32>,
+    pub unimproved_time_limit: Option<f32>,
+    pub seed: Option<u64>,
+}
+
+impl CliOptions {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Brak wartości opcji {}", arg))
+            };
+            let invalid = |value: String| format!("Niepoprawna wartość opcji {}: {}", arg, value);
+            match arg.as_str() {
+                "--previous" => options.previous_plan = Some(value()?),
+                "--iterations" => {
+                    let value = value()?;
+                    options.iterations = Some(value.parse().map_err(|_| invalid(value))?);
+                }
+                "--cooling" => options.cooling = Some(value()?.parse()?),
+                "--initial-temperature" => {
+                    let value = value()?;
+                    options.initial_temperature = Some(value.parse().map_err(|_| invalid(value))?);
+                }
+                "--time-limit" => {
+                    let value = value()?;
+                    options.time_limit = Some(value.parse().map_err(|_| invalid(value))?);
+                }
+                "--unimproved-time-limit" => {
+                    let value = value()?;
+                    options.unimproved_time_limit =
+                        Some(value.parse().map_err(|_| invalid(value))?);
+                }
+                "--seed" => {
+                    let value = value()?;
+                    options.seed = Some(value.parse().map_err(|_| invalid(value))?);
+                }
+                _ => return Err(format!("Nieznana opcja: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    pub fn apply(&self, settings: &mut AnnealingSettings) {
+        if let Some(iterations) = self.iterations {
+            settings.iterations = iterations;
+        }
+        if let Some(cooling) = self.cooling {
+            settings.cooling = cooling;
+        }
+        if let Some(initial_temperature) = self.initial_temperature {
+            settings.initial_temperature = Some(initial_temperature);
+        }
+        if let Some(time_limit) = self.time_limit {
+            settings.time_limit = Some(time_limit);
+        }
+        if let Some(unimproved_time_limit) = self.unimproved_time_limit {
+            settings.unimproved_time_limit = Some(unimproved_time_limit);
+        }
+        if let Some(seed) = self.seed {
+            settings.seed = Some(seed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn previous_plan_is_parsed() {
+        assert_eq!(CliOptions::parse(args(&[])), Ok(CliOptions::default()));
+        assert_eq!(
+            CliOptions::parse(args(&["--previous", "output.json"])),
+            Ok(CliOptions {
+                previous_plan: Some("output.json".to_owned()),
+                ..Default::default()
+            })
+        );
+        assert!(CliOptions::parse(args(&["--previous"])).is_err());
+        assert!(CliOptions::parse(args(&["--foo"])).is_err());
+    }
+
+    #[test]
+    fn annealing_options_replace_input_settings() {
+        let options = CliOptions::parse(args(&[
+            "--cooling",
+            "lundy_mees",
+            "--initial-temperature",
+            "2.5",
+            "--time-limit",
+            "60",
+            "--seed",
+            "42",
+        ]))
+        .unwrap();
+        let mut settings = AnnealingSettings {
+            iterations: 1000,
+            ..Default::default()
+        };
+        options.apply(&mut settings);
+        assert_eq!(settings.iterations, 1000);
+        assert_eq!(settings.cooling, CoolingKind::LundyMees);
+        assert_eq!(settings.initial_temperature, Some(2.5));
+        assert_eq!(settings.time_limit, Some(60.0));
+        assert_eq!(settings.unimproved_time_limit, None);
+        assert_eq!(settings.seed, Some(42));
+
+        assert!(CliOptions::parse(args(&["--cooling", "fast"])).is_err());
+        assert!(CliOptions::parse(args(&["--iterations", "-1"])).is_err());
+    }
+}
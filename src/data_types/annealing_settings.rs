@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+// Sposób obniżania temperatury w trakcie wyżarzania
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoolingKind {
+    #[default]
+    Geometric,
+    Linear,
+    Logarithmic,
+    LundyMees,
+    // Temperatura dobierana do docelowego odsetka przyjętych pogorszeń
+    Adaptive,
+}
+
+impl std::str::FromStr for CoolingKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "geometric" => Ok(Self::Geometric),
+            "linear" => Ok(Self::Linear),
+            "logarithmic" => Ok(Self::Logarithmic),
+            "lundy_mees" => Ok(Self::LundyMees),
+            "adaptive" => Ok(Self::Adaptive),
+            _ => Err(format!("Nieznany sposób chłodzenia: {}", s)),
+        }
+    }
+}
+
+// Parametry wyżarzania. Opcje wiersza poleceń zastępują wartości z danych wejściowych.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnnealingSettings {
+    // Wyżarzanie kończy się po `iterations` przyjętych mutacjach albo po `time_limit`
+    // sekundach, zależnie od tego, co nastąpi wcześniej. Chłodzenie jest rozłożone
+    // tak, żeby skończyło się przy pierwszym z tych limitów.
+    pub iterations: usize,
+    pub time_limit: Option<f32>,
+    // Koniec wyżarzania, jeśli najlepszy plan nie poprawił się przez tyle sekund
+    pub unimproved_time_limit: Option<f32>,
+    pub cooling: CoolingKind,
+    // Jeśli nie jest podana, jest wyznaczana z próbki zmian energii tak, żeby
+    // na początku pogorszenia były przyjmowane z prawdopodobieństwem
+    // `initial_acceptance`
+    pub initial_temperature: Option<f32>,
+    pub final_temperature: f32,
+    pub initial_acceptance: f32,
+    // Po tylu przyjętych mutacjach bez zmiany energii albo tylu odrzuconych z rzędu
+    // wyżarzanie uznaje się za utknięte
+    pub stagnation_limit: usize,
+    // Ile razy utknięte wyżarzanie jest podgrzewane, zanim się zakończy
+    pub max_reheats: u32,
+    // Temperatura po podgrzaniu jako część temperatury początkowej
+    pub reheat_fraction: f32,
+    // Czy po podgrzaniu wrócić do najlepszego dotąd planu
+    pub restart_from_best: bool,
+    // Ziarno generatora liczb losowych. Jeśli nie jest podane, jest losowane
+    // i zapisywane w wyniku, żeby dało się powtórzyć obliczenia. Przy limicie
+    // czasu wynik zależy też od szybkości komputera.
+    pub seed: Option<u64>,
+}
+
+impl Default for AnnealingSettings {
+    fn default() -> Self {
+        Self {
+            iterations: 10_000_000,
+            time_limit: None,
+            unimproved_time_limit: None,
+            cooling: CoolingKind::default(),
+            initial_temperature: None,
+            final_temperature: 0.01,
+            initial_acceptance: 0.8,
+            stagnation_limit: 1_000_000,
+            max_reheats: 5,
+            reheat_fraction: 0.5,
+            restart_from_best: true,
+            seed: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cooling_kind_names_match_serde() {
+        for kind in [
+            CoolingKind::Geometric,
+            CoolingKind::Linear,
+            CoolingKind::Logarithmic,
+            CoolingKind::LundyMees,
+            CoolingKind::Adaptive,
+        ] {
+            let name = serde_json::to_value(kind).unwrap();
+            assert_eq!(name.as_str().unwrap().parse(), Ok(kind));
+        }
+        assert!("exponential".parse::<CoolingKind>().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Weekday {
+    #[default]
+    Poniedzialek,
+    Wtorek,
+    Sroda,
+    Czwartek,
+    Piatek,
+    Sobota,
+    Niedziela,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Self::Poniedzialek,
+        Self::Wtorek,
+        Self::Sroda,
+        Self::Czwartek,
+        Self::Piatek,
+        Self::Sobota,
+        Self::Niedziela,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&x| x == self).unwrap()
+    }
+
+    // Dzień tygodnia wypadający `days` dni później
+    pub fn plus_days(self, days: u32) -> Self {
+        Self::ALL[(self.index() + days as usize) % 7]
+    }
+}
+
+// Data kalendarzowa, w danych wejściowych i wyjściowych zapisywana jako "2021-10-02"
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct CalendarDate {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl CalendarDate {
+    pub fn new(year: i32, month: u8, day: u8) -> Self {
+        assert!(
+            (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month),
+            "Niepoprawna data"
+        );
+        Self { year, month, day }
+    }
+
+    // Liczba dni od 1970-01-01 (algorytm "days from civil" Howarda Hinnanta)
+    fn days_since_epoch(&self) -> i64 {
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    fn of_days_since_epoch(days: i64) -> Self {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Self::new(year as i32, month as u8, day as u8)
+    }
+
+    pub fn plus_days(&self, days: u32) -> Self {
+        Self::of_days_since_epoch(self.days_since_epoch() + days as i64)
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        // 1970-01-01 był czwartkiem
+        Weekday::Czwartek.plus_days(self.days_since_epoch().rem_euclid(7) as u32)
+    }
+}
+
+impl TryFrom<String> for CalendarDate {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let error = || format!("Niepoprawna data: {}", value);
+        let mut parts = value.trim().splitn(3, '-');
+        let mut next = || parts.next().ok_or_else(error);
+        let year = next()?.parse::<i32>().map_err(|_| error())?;
+        let month = next()?.parse::<u8>().map_err(|_| error())?;
+        let day = next()?.parse::<u8>().map_err(|_| error())?;
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return Err(error());
+        }
+        Ok(Self { year, month, day })
+    }
+}
+
+impl From<CalendarDate> for String {
+    fn from(value: CalendarDate) -> Self {
+        format!("{:04}-{:02}-{:02}", value.year, value.month, value.day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weekday_of_known_dates() {
+        assert_eq!(CalendarDate::new(2021, 10, 2).weekday(), Weekday::Sobota);
+        assert_eq!(CalendarDate::new(2000, 2, 29).weekday(), Weekday::Wtorek);
+        assert_eq!(Weekday::Sobota.plus_days(2), Weekday::Poniedzialek);
+    }
+
+    #[test]
+    fn plus_days_crosses_months_and_years() {
+        let date = CalendarDate::new(2021, 12, 31);
+        assert_eq!(date.plus_days(1), CalendarDate::new(2022, 1, 1));
+        assert_eq!(
+            CalendarDate::new(2024, 2, 28).plus_days(2),
+            CalendarDate::new(2024, 3, 1)
+        );
+    }
+
+    #[test]
+    fn invalid_date_is_rejected() {
+        assert!(CalendarDate::try_from("2021-02-29".to_owned()).is_err());
+        assert!(CalendarDate::try_from("2021-13-01".to_owned()).is_err());
+        assert_eq!(
+            CalendarDate::try_from("2024-02-29".to_owned()),
+            Ok(CalendarDate::new(2024, 2, 29))
+        );
+    }
+}
@@ -1,7 +1,36 @@
 use serde::{Deserialize, Serialize};
 
+use super::{Subject, SubjectType};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Classroom {
     pub name: String,
     pub capacity: i32,
+    // Rodzaje zajęć, które mogą się odbywać w sali. Pusta lista (także brak pola
+    // w danych wejściowych) oznacza salę ogólnego przeznaczenia, w której mogą
+    // odbywać się wszystkie zajęcia, również laboratoria. Walidacja ostrzega
+    // o takich salach, jeśli inne sale mają podane rodzaje.
+    #[serde(default)]
+    pub kinds: Vec<SubjectType>,
+    // Wyposażenie sali, np. "komputery", "projektor"
+    #[serde(default)]
+    pub equipment: Vec<String>,
+}
+
+impl Classroom {
+    #[cfg(test)]
+    pub fn new(name: String, capacity: i32) -> Self {
+        Self {
+            name,
+            capacity,
+            kinds: Vec::new(),
+            equipment: Vec::new(),
+        }
+    }
+
+    pub fn can_hold_subject(&self, subject: &Subject) -> bool {
+        let kind_matches = self.kinds.is_empty() || self.kinds.contains(&subject.subject_type);
+        let has_equipment = subject.requires.iter().all(|x| self.equipment.contains(x));
+        kind_matches && has_equipment
+    }
 }
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+// Twarde ograniczenia liczby jednostek czasu zajęć grupy albo nauczyciela.
+// Niepodane pole nie ogranicza.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LessonLimits {
+    // Dotyczy każdego dnia każdego zjazdu, także dnia bez zajęć
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_lessons_in_day: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_lessons_in_day: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_lessons_in_session: Option<u16>,
+}
+
+impl LessonLimits {
+    // Ograniczenia z pól podanych tutaj, a pozostałe z `default`
+    pub fn or(self, default: LessonLimits) -> LessonLimits {
+        LessonLimits {
+            min_lessons_in_day: self.min_lessons_in_day.or(default.min_lessons_in_day),
+            max_lessons_in_day: self.max_lessons_in_day.or(default.max_lessons_in_day),
+            max_lessons_in_session: self
+                .max_lessons_in_session
+                .or(default.max_lessons_in_session),
+        }
+    }
+
+    // Liczba jednostek czasu ponad limit albo brakujących do minimum
+    pub fn day_violations(&self, lessons: u32) -> u32 {
+        let excess = self
+            .max_lessons_in_day
+            .map_or(0, |x| lessons.saturating_sub(x as u32));
+        let shortage = self
+            .min_lessons_in_day
+            .map_or(0, |x| (x as u32).saturating_sub(lessons));
+        excess + shortage
+    }
+
+    pub fn session_violations(&self, lessons: u32) -> u32 {
+        self.max_lessons_in_session
+            .map_or(0, |x| lessons.saturating_sub(x as u32))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HardConstraints {
+    // Ograniczenia wszystkich grup i wszystkich nauczycieli
+    pub group_limits: LessonLimits,
+    pub teacher_limits: LessonLimits,
+    // Klucze to nazwy grup i nauczycieli
+    pub groups: BTreeMap<String, LessonLimits>,
+    pub teachers: BTreeMap<String, LessonLimits>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_count_lessons_outside_bounds() {
+        let limits = LessonLimits {
+            min_lessons_in_day: Some(2),
+            max_lessons_in_day: Some(4),
+            max_lessons_in_session: Some(6),
+        };
+        assert_eq!(limits.day_violations(0), 2);
+        assert_eq!(limits.day_violations(1), 1);
+        assert_eq!(limits.day_violations(3), 0);
+        assert_eq!(limits.day_violations(6), 2);
+        assert_eq!(limits.session_violations(8), 2);
+        assert_eq!(LessonLimits::default().day_violations(100), 0);
+
+        let merged = LessonLimits {
+            max_lessons_in_day: Some(3),
+            ..Default::default()
+        }
+        .or(limits);
+        assert_eq!(merged.max_lessons_in_day, Some(3));
+        assert_eq!(merged.min_lessons_in_day, Some(2));
+    }
+}
@@ -1,13 +1,31 @@
+mod annealing_settings;
+mod calendar_date;
 mod classroom;
-mod lesson;
+mod lesson_limits;
+mod pinned_lesson;
+mod previous_plan;
+mod session;
+mod shared_lesson;
 mod simple_date;
+mod soft_constraints;
 mod student_group;
 mod subject;
 mod teacher;
+mod time_grid;
+mod time_preference;
 
+pub use annealing_settings::*;
+pub use calendar_date::*;
 pub use classroom::*;
-pub use lesson::*;
+pub use lesson_limits::*;
+pub use pinned_lesson::*;
+pub use previous_plan::*;
+pub use session::*;
+pub use shared_lesson::*;
 pub use simple_date::*;
+pub use soft_constraints::*;
 pub use student_group::*;
 pub use subject::*;
 pub use teacher::*;
+pub use time_grid::*;
+pub use time_preference::*;
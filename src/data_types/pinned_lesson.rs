@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use super::ClockTime;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct PinnedTime {
+    #[serde(default)]
+    pub session: u8,
+    pub day: u8, // Dzień od początku zjazdu
+    pub start: ClockTime,
+}
+
+// Zajęcia ustalone z góry. Przypięty termin, nauczyciel i sala nie są zmieniane
+// w trakcie wyżarzania, a niepodane są wybierane jak dla innych zajęć. Przypięcie
+// dotyczy jednej, pierwszej jeszcze nieprzypiętej lekcji przedmiotu tych grup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedLesson {
+    pub subject: String,
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub time: Option<PinnedTime>,
+    #[serde(default)]
+    pub teacher: Option<String>,
+    #[serde(default)]
+    pub classroom: Option<String>,
+}
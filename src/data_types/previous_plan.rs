@@ -0,0 +1,22 @@
+use serde::Deserialize;
+
+use super::ClockTime;
+
+// Plan zapisany wcześniej do output.json, używany jako rozwiązanie początkowe
+// przy układaniu planu od nowa. Pozostałe pola PlanOutput są pomijane.
+#[derive(Debug, Deserialize)]
+pub struct PreviousPlan {
+    pub lessons: Vec<PreviousLesson>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviousLesson {
+    pub groups: Vec<String>,
+    pub teacher: String,
+    pub classroom: String,
+    pub subject_name: String,
+    #[serde(default)]
+    pub session: u8,
+    pub day: u8, // Dzień od początku zjazdu
+    pub start: ClockTime,
+}
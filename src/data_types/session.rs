@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+use super::CalendarDate;
+
+// Zjazd, czyli kilka kolejnych dni zajęć (PlanInput::days) zaczynających się od `start_date`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Session {
+    pub start_date: CalendarDate,
+}
@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+// Zajęcia (zwykle wykład), na które kilka grup przychodzi jednocześnie.
+// Grupy nie powinny dodatkowo wymieniać tego przedmiotu w swoich `subjects`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SharedLesson {
+    pub subject: String,
+    pub groups: Vec<String>,
+}
@@ -1,73 +1,69 @@
-use std::collections::HashSet;
+use std::convert::TryFrom;
 
 use serde::{Deserialize, Serialize};
 
+use super::ClockTime;
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Hash)]
+#[serde(try_from = "RawSimpleDate")]
 pub struct SimpleDate {
     pub day: u8, // Dzień od początku zjazdu
     pub hour: u8,
+    pub minute: u8,
 }
 
-impl SimpleDate {
-    pub fn new(day: u8, hour: u8) -> Self {
-        assert!(hour < 23, "Niepoprawna godzina");
-        assert!(
-            hour % 2 == 0,
-            "Początek zajęć musi być o parzystej godzinie"
-        );
-        Self { day, hour }
-    }
-
-    pub fn from_u8_time(time: u8) -> Self {
-        let day = time / 6;
-        let hour = time % 6;
-        Self { day, hour }
-    }
+// Termin w postaci z danych wejściowych, przed sprawdzeniem godziny
+#[derive(Deserialize)]
+struct RawSimpleDate {
+    day: u8,
+    hour: u8,
+    #[serde(default)]
+    minute: u8,
 }
 
-#[derive(Debug, Clone)]
-pub struct DateList {
-    // illegal_dates: HashSet<SimpleDate>,
-    days: u8,
-    current_day: u8,
-    current_hour: u8,
-}
+impl TryFrom<RawSimpleDate> for SimpleDate {
+    type Error = String;
 
-impl DateList {
-    pub fn new(/*illegal_dates: HashSet<SimpleDate>, */ days: u8) -> Self {
-        Self {
-            // illegal_dates,
-            days,
-            current_day: 0,
-            current_hour: 8,
+    fn try_from(value: RawSimpleDate) -> Result<Self, Self::Error> {
+        if value.hour >= 24 || value.minute >= 60 {
+            return Err(format!(
+                "Niepoprawna godzina {}:{:02} w dniu {}",
+                value.hour, value.minute, value.day
+            ));
         }
+        Ok(Self {
+            day: value.day,
+            hour: value.hour,
+            minute: value.minute,
+        })
     }
 }
 
-impl DateList {
-    fn set_next(&mut self) {
-        self.current_hour += 2;
-        if self.current_hour > 20 {
-            self.current_hour = 8;
-            self.current_day += 1;
-        }
+impl SimpleDate {
+    #[cfg(test)]
+    pub fn new(day: u8, hour: u8, minute: u8) -> Self {
+        assert!(hour < 24 && minute < 60, "Niepoprawna godzina");
+        Self { day, hour, minute }
     }
 
-    fn is_done(&self) -> bool {
-        self.current_day == self.days
+    pub fn clock_time(&self) -> ClockTime {
+        ClockTime::new(self.hour, self.minute)
     }
 }
 
-impl Iterator for DateList {
-    type Item = SimpleDate;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.is_done() {
-            None
-        } else {
-            let next = SimpleDate::new(self.current_day, self.current_hour);
-            self.set_next();
-            Some(next)
-        }
+    #[test]
+    fn invalid_hour_is_rejected() {
+        let date = serde_json::from_str::<SimpleDate>(r#"{"day": 1, "hour": 11}"#).unwrap();
+        assert_eq!(date, SimpleDate::new(1, 11, 0));
+
+        let err = serde_json::from_str::<SimpleDate>(r#"{"day": 0, "hour": 30}"#).unwrap_err();
+        assert!(err.to_string().contains("30:00"), "{}", err);
+        assert!(
+            serde_json::from_str::<SimpleDate>(r#"{"day": 0, "hour": 8, "minute": 60}"#).is_err()
+        );
     }
 }
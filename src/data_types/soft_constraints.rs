@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::TimePreference;
+
+// Wagi składowych energii (miękkich ograniczeń)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnergyWeights {
+    pub student_gap_weight: f32,
+    pub teacher_gap_weight: f32,
+    pub teacher_lessons_in_day_weight: f32,
+    pub group_lessons_in_day_weight: f32,
+    pub session_balance_weight: f32,
+    pub pattern_stability_weight: f32,
+    pub time_preference_weight: f32,
+    // Kara za każdą lekcję przesuniętą względem poprzedniego planu
+    pub stability_weight: f32,
+}
+
+impl Default for EnergyWeights {
+    fn default() -> Self {
+        Self {
+            student_gap_weight: 1.0,
+            teacher_gap_weight: 1.0,
+            teacher_lessons_in_day_weight: 1.0,
+            group_lessons_in_day_weight: 1.0,
+            // Nierówny rozkład na zjazdy jest gorszy niż kilka zajęć za dużo w dniu
+            session_balance_weight: 10.0,
+            pattern_stability_weight: 1.0,
+            time_preference_weight: 1.0,
+            stability_weight: 1.0,
+        }
+    }
+}
+
+impl EnergyWeights {
+    pub fn named(&self) -> [(&'static str, f32); 8] {
+        [
+            ("student_gap_weight", self.student_gap_weight),
+            ("teacher_gap_weight", self.teacher_gap_weight),
+            (
+                "teacher_lessons_in_day_weight",
+                self.teacher_lessons_in_day_weight,
+            ),
+            (
+                "group_lessons_in_day_weight",
+                self.group_lessons_in_day_weight,
+            ),
+            ("session_balance_weight", self.session_balance_weight),
+            ("pattern_stability_weight", self.pattern_stability_weight),
+            ("time_preference_weight", self.time_preference_weight),
+            ("stability_weight", self.stability_weight),
+        ]
+    }
+}
+
+// Ustawienia jednej grupy albo nauczyciela zastępujące ustawienia ogólne
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DayOverride {
+    pub ideal_lessons_in_day: Option<u8>,
+    pub gap_weight: Option<f32>,
+    pub lessons_in_day_weight: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoftConstraints {
+    pub weights: EnergyWeights,
+    // Liczba jednostek czasu zajęć w dniu, do której dąży plan
+    pub ideal_lessons_in_day: u8,
+    // Klucze to nazwy grup i nauczycieli
+    pub groups: BTreeMap<String, DayOverride>,
+    pub teachers: BTreeMap<String, DayOverride>,
+    pub time_preferences: Vec<TimePreference>,
+}
+
+impl Default for SoftConstraints {
+    fn default() -> Self {
+        Self {
+            weights: EnergyWeights::default(),
+            ideal_lessons_in_day: 4,
+            groups: BTreeMap::new(),
+            teachers: BTreeMap::new(),
+            time_preferences: Vec::new(),
+        }
+    }
+}
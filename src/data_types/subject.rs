@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum SubjectType {
     Laboratorium,
     Cwiczenia,
@@ -8,19 +8,37 @@ pub enum SubjectType {
     Projekt,
 }
 
+fn default_duration() -> u8 {
+    1
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Subject {
     pub name: String,
     pub subject_type: SubjectType,
     pub count: i8, // Ilość zajęć tego typu w zjeździe
+    // Łączna liczba godzin lekcyjnych (po 45 minut) w semestrze. Jeśli jest
+    // podana, zastępuje `count`, a zajęcia są rozkładane na wszystkie zjazdy.
+    #[serde(default)]
+    pub semester_hours: Option<u16>,
+    // Liczba kolejnych jednostek czasu zajmowanych przez jedne zajęcia
+    #[serde(default = "default_duration")]
+    pub duration: u8,
+    // Wyposażenie, którego wymagają zajęcia, np. "komputery"
+    #[serde(default)]
+    pub requires: Vec<String>,
 }
 
 impl Subject {
+    #[cfg(test)]
     pub fn new(name: String, subject_type: SubjectType, count: i8) -> Self {
         Self {
             name,
             subject_type,
             count,
+            semester_hours: None,
+            duration: default_duration(),
+            requires: Vec::new(),
         }
     }
 }
@@ -7,6 +7,7 @@ pub struct Teacher {
 }
 
 impl Teacher {
+    #[cfg(test)]
     pub fn new(name: String, can_teach: Vec<String>) -> Self {
         Self { name, can_teach }
     }
@@ -0,0 +1,312 @@
+use std::{collections::HashMap, convert::TryFrom};
+
+use serde::{Deserialize, Serialize};
+
+use super::SimpleDate;
+
+// Godzina w ciągu dnia, w danych wejściowych i wyjściowych zapisywana jako "8:00"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ClockTime {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl ClockTime {
+    pub fn new(hour: u8, minute: u8) -> Self {
+        assert!(hour < 24 && minute < 60, "Niepoprawna godzina");
+        Self { hour, minute }
+    }
+
+    pub fn minutes(&self) -> u16 {
+        self.hour as u16 * 60 + self.minute as u16
+    }
+
+    pub fn of_minutes(minutes: u16) -> Self {
+        Self::new((minutes / 60) as u8, (minutes % 60) as u8)
+    }
+}
+
+impl TryFrom<String> for ClockTime {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let error = || format!("Niepoprawna godzina: {}", value);
+        let (hour, minute) = value.split_once(':').ok_or_else(error)?;
+        let hour = hour.trim().parse::<u8>().map_err(|_| error())?;
+        let minute = minute.trim().parse::<u8>().map_err(|_| error())?;
+        if hour >= 24 || minute >= 60 {
+            return Err(error());
+        }
+        Ok(Self { hour, minute })
+    }
+}
+
+impl From<ClockTime> for String {
+    fn from(value: ClockTime) -> Self {
+        format!("{}:{:02}", value.hour, value.minute)
+    }
+}
+
+// Podział dnia na jednostki czasu, w których mogą zaczynać się zajęcia
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeGrid {
+    pub slot_length: u16, // W minutach
+    pub slot_starts: Vec<ClockTime>,
+    // Dni (liczone od początku zjazdu) o innym podziale, np. krótsza niedziela
+    #[serde(default)]
+    pub day_slot_starts: HashMap<u8, Vec<ClockTime>>,
+}
+
+impl Default for TimeGrid {
+    // Sześć dwugodzinnych zajęć od 8:00 do 20:00
+    fn default() -> Self {
+        Self {
+            slot_length: 120,
+            slot_starts: (0..6).map(|x| ClockTime::new(8 + 2 * x, 0)).collect(),
+            day_slot_starts: HashMap::new(),
+        }
+    }
+}
+
+impl TimeGrid {
+    pub fn slot_starts_of_day(&self, day: u8) -> &[ClockTime] {
+        self.day_slot_starts.get(&day).unwrap_or(&self.slot_starts)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub session: u8,
+    pub day: u8,   // Dzień od początku zjazdu
+    pub index: u8, // Numer zajęć w ciągu dnia
+    pub start: ClockTime,
+}
+
+// Wszystkie jednostki czasu semestru po kolei, zjazd po zjeździe. Indeks w `slots`
+// to czas (`time`) używany w trakcie wyżarzania.
+#[derive(Debug, Clone, Default)]
+pub struct SlotTable {
+    slots: Vec<Slot>,
+    slot_length: u16,
+    days: u8,
+    sessions: u8,
+}
+
+impl SlotTable {
+    pub fn new(time_grid: &TimeGrid, days: u8, sessions: u8) -> Result<Self, String> {
+        if time_grid.slot_length == 0 {
+            return Err("Długość zajęć musi być większa od zera".to_owned());
+        }
+        // Podział dnia spoza zjazdu zostałby pominięty
+        if let Some(day) = time_grid
+            .day_slot_starts
+            .keys()
+            .filter(|&&x| x >= days)
+            .min()
+        {
+            return Err(format!(
+                "Podział dnia {} dotyczy dnia spoza zjazdu (zjazd ma {} dni)",
+                day, days
+            ));
+        }
+
+        let mut session_slots = Vec::new();
+        for day in 0..days {
+            let starts = time_grid.slot_starts_of_day(day);
+            if starts.is_empty() {
+                return Err(format!("Dzień {} nie ma żadnych godzin zajęć", day));
+            }
+            // Zajęcia w dniu są zapisywane w energii jako maska bitowa
+            if starts.len() > 128 {
+                return Err(format!(
+                    "Zbyt wiele godzin zajęć w dniu {} (maksymalnie 128)",
+                    day
+                ));
+            }
+            for pair in starts.windows(2) {
+                if pair[1] <= pair[0] {
+                    return Err(format!(
+                        "Godziny zajęć w dniu {} nie są podane rosnąco: {} po {}",
+                        day,
+                        String::from(pair[1]),
+                        String::from(pair[0])
+                    ));
+                }
+                if pair[0].minutes() + time_grid.slot_length > pair[1].minutes() {
+                    return Err(format!(
+                        "Zajęcia o {} i {} w dniu {} nachodzą na siebie",
+                        String::from(pair[0]),
+                        String::from(pair[1]),
+                        day
+                    ));
+                }
+            }
+            // Godzina zakończenia zajęć musi dać się zapisać jako ClockTime,
+            // więc nie może to być 24:00
+            let last = starts[starts.len() - 1];
+            if last.minutes() + time_grid.slot_length >= 24 * 60 {
+                return Err(format!(
+                    "Zajęcia o {} w dniu {} nie kończą się przed północą",
+                    String::from(last),
+                    day
+                ));
+            }
+            session_slots.extend(starts.iter().enumerate().map(|(index, &start)| Slot {
+                session: 0,
+                day,
+                index: index as u8,
+                start,
+            }));
+        }
+
+        // Każdy zjazd ma ten sam podział na dni i godziny
+        let slots = (0..sessions)
+            .flat_map(|session| {
+                session_slots
+                    .iter()
+                    .map(move |&slot| Slot { session, ..slot })
+            })
+            .collect::<Vec<_>>();
+
+        if slots.len() > u16::MAX as usize {
+            return Err(format!(
+                "Zbyt wiele jednostek czasu w semestrze: {} (maksymalnie {})",
+                slots.len(),
+                u16::MAX
+            ));
+        }
+
+        Ok(Self {
+            slots,
+            slot_length: time_grid.slot_length,
+            days,
+            sessions,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn sessions(&self) -> u8 {
+        self.sessions
+    }
+
+    // Liczba dni we wszystkich zjazdach
+    pub fn day_count(&self) -> u16 {
+        self.sessions as u16 * self.days as u16
+    }
+
+    pub fn slot(&self, time: u16) -> Slot {
+        self.slots[time as usize]
+    }
+
+    // Numer dnia liczony od początku semestru, różny dla tych samych dni różnych zjazdów
+    pub fn day(&self, time: u16) -> u16 {
+        let slot = self.slot(time);
+        slot.session as u16 * self.days as u16 + slot.day as u16
+    }
+
+    // Miejsce w planie zjazdu (dzień i numer zajęć), takie samo w każdym zjeździe
+    pub fn position(&self, time: u16) -> (u8, u8) {
+        let slot = self.slot(time);
+        (slot.day, slot.index)
+    }
+
+    // Koniec zajęć trwających `duration` jednostek czasu
+    pub fn end(&self, time: u16, duration: u8) -> ClockTime {
+        let last = self.slot(time + duration as u16 - 1).start;
+        ClockTime::of_minutes(last.minutes() + self.slot_length)
+    }
+
+    // Czy jednostka czasu obejmuje podaną godzinę (w każdym zjeździe)
+    pub fn contains(&self, time: u16, date: SimpleDate) -> bool {
+        let slot = self.slot(time);
+        let minutes = date.clock_time().minutes();
+        slot.day == date.day
+            && slot.start.minutes() <= minutes
+            && minutes < slot.start.minutes() + self.slot_length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shorter_day_has_fewer_slots() {
+        let mut time_grid = TimeGrid::default();
+        time_grid
+            .day_slot_starts
+            .insert(1, vec![ClockTime::new(8, 0), ClockTime::new(10, 0)]);
+        let slots = SlotTable::new(&time_grid, 2, 1).unwrap();
+
+        assert_eq!(slots.len(), 8);
+        assert_eq!(slots.day(5), 0);
+        assert_eq!(
+            slots.slot(7),
+            Slot {
+                session: 0,
+                day: 1,
+                index: 1,
+                start: ClockTime::new(10, 0)
+            }
+        );
+        assert_eq!(slots.end(6, 2), ClockTime::new(12, 0));
+    }
+
+    #[test]
+    fn sessions_repeat_the_same_days() {
+        let slots = SlotTable::new(&TimeGrid::default(), 2, 3).unwrap();
+
+        assert_eq!(slots.len(), 3 * 2 * 6);
+        assert_eq!(slots.slot(13).session, 1);
+        assert_eq!(slots.position(13), slots.position(1));
+        assert_eq!(slots.day(13), 2);
+        assert_ne!(slots.day(13), slots.day(1));
+    }
+
+    #[test]
+    fn overlapping_slots_are_rejected() {
+        let time_grid = TimeGrid {
+            slot_length: 90,
+            slot_starts: vec![ClockTime::new(8, 0), ClockTime::new(9, 0)],
+            day_slot_starts: HashMap::new(),
+        };
+        assert!(SlotTable::new(&time_grid, 1, 1).is_err());
+    }
+
+    #[test]
+    fn unsorted_slots_and_unknown_days_are_rejected() {
+        let mut time_grid = TimeGrid {
+            slot_length: 60,
+            slot_starts: vec![ClockTime::new(10, 0), ClockTime::new(8, 0)],
+            day_slot_starts: HashMap::new(),
+        };
+        let err = SlotTable::new(&time_grid, 1, 1).unwrap_err();
+        assert!(err.contains("rosnąco"), "{}", err);
+
+        time_grid.slot_starts.reverse();
+        time_grid
+            .day_slot_starts
+            .insert(2, vec![ClockTime::new(8, 0)]);
+        let err = SlotTable::new(&time_grid, 2, 1).unwrap_err();
+        assert!(err.contains("dnia 2"), "{}", err);
+        assert!(SlotTable::new(&time_grid, 3, 1).is_ok());
+    }
+
+    #[test]
+    fn slot_ending_at_midnight_is_rejected() {
+        let mut time_grid = TimeGrid {
+            slot_length: 120,
+            slot_starts: vec![ClockTime::new(22, 0)],
+            day_slot_starts: HashMap::new(),
+        };
+        assert!(SlotTable::new(&time_grid, 1, 1).is_err());
+
+        time_grid.slot_length = 119;
+        let slots = SlotTable::new(&time_grid, 1, 1).unwrap();
+        assert_eq!(slots.end(0, 1), ClockTime::new(23, 59));
+    }
+}
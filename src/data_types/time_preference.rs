@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+use crate::illegal_state::IllegalStateSubject;
+
+use super::{ClockTime, Slot, Weekday};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PreferenceKind {
+    // Kara za każdą jednostkę czasu zajęć w podanym czasie
+    #[default]
+    Avoid,
+    // Kara za każdą jednostkę czasu zajęć poza podanym czasem
+    Prefer,
+}
+
+// Miękkie ograniczenie: grupa, nauczyciel albo sala woli nie mieć zajęć w podanym
+// czasie (albo mieć je tylko w nim). Niepodane pola nie zawężają czasu, np. sam
+// `weekday` i `from` oznaczają popołudnia tego dnia tygodnia w każdym zjeździe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimePreference {
+    pub subject: IllegalStateSubject<String>,
+    #[serde(default)]
+    pub kind: PreferenceKind,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<u8>, // Dzień od początku zjazdu
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekday: Option<Weekday>,
+    // Jednostka czasu należy do przedziału, jeśli zaczyna się w [from, to)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<ClockTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<ClockTime>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl TimePreference {
+    // Czy jednostka czasu należy do czasu podanego w preferencji
+    pub fn contains(&self, slot: Slot, weekday: Weekday) -> bool {
+        self.session.is_none_or(|x| x == slot.session)
+            && self.day.is_none_or(|x| x == slot.day)
+            && self.weekday.is_none_or(|x| x == weekday)
+            && self.from.is_none_or(|x| x <= slot.start)
+            && self.to.is_none_or(|x| slot.start < x)
+    }
+
+    // Czy zajęcia w tej jednostce czasu są karane
+    pub fn penalizes(&self, slot: Slot, weekday: Weekday) -> bool {
+        match self.kind {
+            PreferenceKind::Avoid => self.contains(slot, weekday),
+            PreferenceKind::Prefer => !self.contains(slot, weekday),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preference_penalizes_slots_by_kind() {
+        let mut preference = TimePreference {
+            subject: IllegalStateSubject::Teacher("Nowak".to_owned()),
+            kind: PreferenceKind::Avoid,
+            weight: 1.0,
+            session: None,
+            day: None,
+            weekday: Some(Weekday::Niedziela),
+            from: Some(ClockTime::new(14, 0)),
+            to: None,
+        };
+        let slot = |hour| Slot {
+            session: 1,
+            day: 1,
+            index: 0,
+            start: ClockTime::new(hour, 0),
+        };
+
+        assert!(preference.penalizes(slot(14), Weekday::Niedziela));
+        assert!(!preference.penalizes(slot(12), Weekday::Niedziela));
+        assert!(!preference.penalizes(slot(14), Weekday::Sobota));
+
+        preference.kind = PreferenceKind::Prefer;
+        assert!(!preference.penalizes(slot(14), Weekday::Niedziela));
+        assert!(preference.penalizes(slot(14), Weekday::Sobota));
+    }
+}
@@ -1,62 +1,131 @@
-use crate::{annealing::inner_state::Lesson, data_types::SimpleDate};
+use crate::{
+    annealing::inner_state::{Lesson, LessonShape},
+    data_types::{SimpleDate, SlotTable},
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-pub enum IllegalStateSubject {
-    StudentGroup(u8),
-    Teacher(u8),
-    Classroom(u8),
+// Parametr `T` to sposób wskazania grupy, nauczyciela lub sali. W danych
+// wejściowych są to nazwy (`String`), a w trakcie wyżarzania indeksy (`u8`).
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IllegalStateSubject<T = u8> {
+    StudentGroup(T),
+    Teacher(T),
+    Classroom(T),
 }
 
-#[derive(Serialize, Deserialize)]
-pub enum IllegalStateObject {
-    StudentGroup(u8),
-    Teacher(u8),
-    Day(u8),
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum IllegalStateObject<T = u8> {
+    StudentGroup(T),
+    Teacher(T),
+    Day(u8), // Dzień od początku zjazdu, w każdym zjeździe
+    Session(u8),
     DayHour(SimpleDate),
-    Classroom(u8),
+    Classroom(T),
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct IllegalState {
-    pub subject: IllegalStateSubject,
-    pub object: IllegalStateObject,
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IllegalState<T = u8> {
+    pub subject: IllegalStateSubject<T>,
+    pub object: IllegalStateObject<T>,
 }
 
-impl IllegalState {
+impl<T> IllegalState<T> {
     pub fn is_logic_error(&self) -> bool {
-        match &self.subject {
-            &IllegalStateSubject::StudentGroup(_) => match &self.object {
-                _ => return false,
-            },
-            &IllegalStateSubject::Teacher(_) => match &self.object {
-                &IllegalStateObject::Teacher(_) => return true,
-                _ => return false,
-            },
-            &IllegalStateSubject::Classroom(_) => match &self.object {
-                &IllegalStateObject::Classroom(_) => return true,
-                _ => return false,
-            },
+        matches!(
+            (&self.subject, &self.object),
+            (
+                IllegalStateSubject::Teacher(_),
+                IllegalStateObject::Teacher(_)
+            ) | (
+                IllegalStateSubject::Classroom(_),
+                IllegalStateObject::Classroom(_)
+            )
+        )
+    }
+}
+
+impl IllegalStateSubject {
+    // Czy lekcja dotyczy tej grupy, nauczyciela albo sali
+    pub fn is_part_of(&self, lesson: Lesson, shape: &LessonShape) -> bool {
+        match *self {
+            IllegalStateSubject::StudentGroup(x) => shape.groups.contains(&x),
+            IllegalStateSubject::Teacher(x) => lesson.teacher == x,
+            IllegalStateSubject::Classroom(x) => lesson.classroom == x,
         }
     }
+}
 
-    pub fn is_violated_by(&self, lesson: Lesson) -> bool {
-        let contains_subject = match self.subject {
-            IllegalStateSubject::StudentGroup(x) if lesson.group == x => true,
-            IllegalStateSubject::Teacher(x) if lesson.teacher == x => true,
-            IllegalStateSubject::Classroom(x) if lesson.classroom == x => true,
-            _ => false,
-        };
+impl IllegalStateObject {
+    // Czy zakaz obejmuje jednostkę czasu. Dla zakazów niezależnych od terminu `None`.
+    pub fn covers_time(&self, time: u16, slots: &SlotTable) -> Option<bool> {
+        match *self {
+            IllegalStateObject::Day(x) => Some(slots.slot(time).day == x),
+            IllegalStateObject::Session(x) => Some(slots.slot(time).session == x),
+            IllegalStateObject::DayHour(x) => Some(slots.contains(time, x)),
+            _ => None,
+        }
+    }
+}
+
+impl IllegalState {
+    pub fn is_violated_by(&self, lesson: Lesson, shape: &LessonShape, slots: &SlotTable) -> bool {
+        let contains_subject = self.subject.is_part_of(lesson, shape);
 
         let contains_object = match self.object {
-            IllegalStateObject::Day(x) if SimpleDate::from_u8_time(lesson.time).day == x => true,
-            IllegalStateObject::DayHour(x) if SimpleDate::from_u8_time(lesson.time) == x => true,
-            IllegalStateObject::Teacher(x) if lesson.teacher == x => true,
-            IllegalStateObject::Classroom(x) if lesson.classroom == x => true,
-            IllegalStateObject::StudentGroup(x) if lesson.group == x => true,
-            _ => false,
+            IllegalStateObject::Day(x) => slots.slot(lesson.time).day == x,
+            IllegalStateObject::Session(x) => slots.slot(lesson.time).session == x,
+            // Lekcja narusza stan, jeśli trwa w podanej godzinie
+            IllegalStateObject::DayHour(x) => {
+                (0..shape.duration).any(|offset| slots.contains(lesson.time + offset as u16, x))
+            }
+            IllegalStateObject::Teacher(x) => lesson.teacher == x,
+            IllegalStateObject::Classroom(x) => lesson.classroom == x,
+            IllegalStateObject::StudentGroup(x) => shape.groups.contains(&x),
+        };
+
+        contains_subject && contains_object
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn teacher_day_is_violated_only_on_that_day() {
+        let state = IllegalState {
+            subject: IllegalStateSubject::Teacher(1),
+            object: IllegalStateObject::Day(2),
+        };
+        let lesson = Lesson {
+            time: 2 * 6 + 3,
+            teacher: 1,
+            classroom: 0,
+        };
+
+        let shape = LessonShape::new(vec![0], 1, 0);
+        let slots = SlotTable::new(&Default::default(), 3, 1).unwrap();
+
+        assert!(state.is_violated_by(lesson, &shape, &slots));
+        assert!(!state.is_violated_by(lesson.with_time(3), &shape, &slots));
+        assert!(!state.is_violated_by(lesson.with_teacher(0), &shape, &slots));
+    }
+
+    #[test]
+    fn day_hour_is_violated_by_lesson_lasting_through_it() {
+        let state = IllegalState {
+            subject: IllegalStateSubject::Teacher(0),
+            object: IllegalStateObject::DayHour(SimpleDate::new(0, 11, 0)),
+        };
+        let lesson = Lesson {
+            time: 0,
+            teacher: 0,
+            classroom: 0,
         };
+        let slots = SlotTable::new(&Default::default(), 1, 1).unwrap();
 
-        (contains_subject, contains_object) == (true, true)
+        assert!(!state.is_violated_by(lesson, &LessonShape::new(vec![0], 1, 0), &slots));
+        assert!(state.is_violated_by(lesson, &LessonShape::new(vec![0], 2, 0), &slots));
     }
 }
@@ -7,17 +7,38 @@ pub struct PlanInput {
     pub student_groups: Vec<StudentGroup>,
     pub teachers: Vec<Teacher>,
     pub classrooms: Vec<Classroom>,
-    pub illegal_states: Vec<IllegalState>,
+    pub illegal_states: Vec<IllegalState<String>>,
     pub subjects: Vec<Subject>,
-    pub days: u8,
+    #[serde(default)]
+    pub shared_lessons: Vec<SharedLesson>,
+    #[serde(default)]
+    pub pinned_lessons: Vec<PinnedLesson>,
+    pub days: u8, // Liczba dni jednego zjazdu
+    #[serde(default)]
+    pub time_grid: TimeGrid,
+    // Data pierwszego dnia zjazdu; jeśli jest podana, wyznacza też dzień tygodnia.
+    // Używana tylko, gdy nie podano listy zjazdów.
+    #[serde(default)]
+    pub start_date: Option<CalendarDate>,
+    #[serde(default)]
+    pub sessions: Vec<Session>,
+    #[serde(default)]
+    pub first_weekday: Weekday,
+    #[serde(default)]
+    pub soft_constraints: SoftConstraints,
+    #[serde(default)]
+    pub hard_constraints: HardConstraints,
+    #[serde(default)]
+    pub annealing: AnnealingSettings,
 }
 
 impl PlanInput {
+    #[cfg(test)]
     pub fn new(
         student_groups: Vec<StudentGroup>,
         teachers: Vec<Teacher>,
         classrooms: Vec<Classroom>,
-        illegal_states: Vec<IllegalState>,
+        illegal_states: Vec<IllegalState<String>>,
         subjects: Vec<Subject>,
         days: u8,
     ) -> Self {
@@ -27,29 +48,56 @@ impl PlanInput {
             classrooms,
             illegal_states,
             subjects,
+            shared_lessons: Vec::new(),
+            pinned_lessons: Vec::new(),
             days,
+            time_grid: TimeGrid::default(),
+            start_date: None,
+            sessions: Vec::new(),
+            first_weekday: Weekday::default(),
+            soft_constraints: SoftConstraints::default(),
+            hard_constraints: HardConstraints::default(),
+            annealing: AnnealingSettings::default(),
         }
     }
 
     pub fn find_subject(&self, name: &str) -> Option<&Subject> {
-        self.subjects.iter().filter(|x| x.name == name).next()
+        self.subjects.iter().find(|x| x.name == name)
     }
 
-    pub fn possible_lessons(&self) -> Option<Vec<LessonPossible>> {
-        let mut result = Vec::new();
-        for student_group in self.student_groups.iter() {
-            for subject_name in student_group.subjects.iter() {
-                let subject = self.find_subject(subject_name)?;
-                for _ in 0..subject.count {
-                    result.push(LessonPossible::new(
-                        student_group,
-                        subject,
-                        &self.teachers,
-                        &self.classrooms,
-                    ));
-                }
+    // Bez listy zjazdów plan obejmuje jeden zjazd
+    pub fn session_count(&self) -> u8 {
+        self.sessions.len().clamp(1, u8::MAX as usize) as u8
+    }
+
+    fn session_start_date(&self, session: u8) -> Option<CalendarDate> {
+        match self.sessions.get(session as usize) {
+            Some(x) => Some(x.start_date),
+            None => self.start_date,
+        }
+    }
+
+    pub fn weekday_of(&self, session: u8, day: u8) -> Weekday {
+        match self.session_start_date(session) {
+            Some(start_date) => start_date.plus_days(day as u32).weekday(),
+            None => self.first_weekday.plus_days(day as u32),
+        }
+    }
+
+    pub fn date_of(&self, session: u8, day: u8) -> Option<CalendarDate> {
+        self.session_start_date(session)
+            .map(|x| x.plus_days(day as u32))
+    }
+
+    // Liczba zajęć przedmiotu dla jednej grupy w całym semestrze
+    pub fn lesson_count(&self, subject: &Subject) -> usize {
+        match subject.semester_hours {
+            Some(hours) => {
+                let lesson_minutes =
+                    subject.duration.max(1) as u32 * self.time_grid.slot_length as u32;
+                (hours as u32 * 45).div_ceil(lesson_minutes.max(1)) as usize
             }
+            None => subject.count.max(0) as usize * self.session_count() as usize,
         }
-        Some(result)
     }
 }
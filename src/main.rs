@@ -1,45 +1,153 @@
-use std::collections::HashSet;
 use std::fs::File;
 
 mod annealing;
+mod cli;
 mod data_types;
 mod illegal_state;
 mod input;
 mod output;
+mod validation;
 
-use annealing::{adapter::AnnealingAdapter, energy::EnergyWeights};
+use annealing::{adapter::AnnealingAdapter, energy::BufferStatistics};
+use cli::CliOptions;
+use data_types::PreviousPlan;
 use input::PlanInput;
-
-use crate::annealing::illegal_buffer::IllegalBuffer;
+use rand::{rngs::StdRng, SeedableRng};
 
 fn main() {
+    let options = CliOptions::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    let previous_plan = options.previous_plan.as_ref().map(|path| {
+        std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|x| serde_json::from_str::<PreviousPlan>(&x).map_err(|err| err.to_string()))
+            .unwrap_or_else(|err| {
+                eprintln!("Błąd poprzedniego planu {}: {}", path, err);
+                std::process::exit(1);
+            })
+    });
+
     let mut input = String::new();
-    std::io::stdin().read_to_string(&mut input).unwrap();
-    let plan_input = serde_json::from_str::<PlanInput>(&input).unwrap();
+    let mut plan_input = std::io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|err| err.to_string())
+        .and_then(|_| serde_json::from_str::<PlanInput>(&input).map_err(|err| err.to_string()))
+        .unwrap_or_else(|err| {
+            eprintln!("Błąd danych wejściowych: {}", err);
+            std::process::exit(1);
+        });
 
-    let annealing_adapter = AnnealingAdapter::of_plan_input(&plan_input);
-    let mut buffer = annealing_adapter.create_annealing_buffer();
+    options.apply(&mut plan_input.annealing);
+    let report = validation::validate(&plan_input);
+    for warning in &report.warnings {
+        eprintln!("Ostrzeżenie: {}", warning);
+    }
+    for error in &report.errors {
+        eprintln!("Błąd: {}", error);
+    }
+    if !report.is_valid() {
+        eprintln!(
+            "Nie można ułożyć planu, dane wejściowe zawierają błędy ({})",
+            report.errors.len()
+        );
+        std::process::exit(1);
+    }
+
+    let (annealing_adapter, illegal_buffer, parameters) =
+        AnnealingAdapter::of_plan_input(&plan_input)
+            .and_then(|mut adapter| {
+                if let Some(previous_plan) = &previous_plan {
+                    let found = adapter.warm_start(previous_plan);
+                    println!(
+                        "Odnaleziono {} z {} zajęć poprzedniego planu",
+                        found,
+                        previous_plan.lessons.len()
+                    );
+                }
+                let illegal_buffer = adapter.create_illegal_buffer()?;
+                let parameters = adapter.energy_parameters()?;
+                Ok((adapter, illegal_buffer, parameters))
+            })
+            .unwrap_or_else(|err| {
+                eprintln!("Błąd danych wejściowych: {}", err);
+                std::process::exit(1);
+            });
+    let mut buffer = annealing_adapter.create_annealing_buffer(&illegal_buffer);
+    let unplaced = buffer
+        .inner_state
+        .state_ref()
+        .placed
+        .iter()
+        .filter(|&&x| !x)
+        .count();
+    if unplaced > 0 {
+        println!(
+            "Nie udało się umieścić {} zajęć w planie początkowym",
+            unplaced
+        );
+    }
 
     buffer.assert_maps_synchronized("After adapter::create_annealing_buffer");
 
-    buffer.anneal_iterations(
-        10_000_000,
-        &EnergyWeights {
-            student_gap_weight: 1.0,
-            teacher_gap_weight: 1.0,
-            group_lessons_in_day_weight: 1.0,
-            teacher_lessons_in_day_weight: 1.0,
-        },
-        IllegalBuffer::new(HashSet::new(), HashSet::new(), Vec::new()),
-    );
+    let mut settings = plan_input.annealing.clone();
+    let seed = *settings.seed.get_or_insert_with(rand::random);
+    println!("Ziarno generatora liczb losowych: {}", seed);
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let run = buffer.anneal_iterations(&settings, &parameters, illegal_buffer, &mut rng);
+    let temperature_given = settings.initial_temperature.is_some();
+    let score = run.score;
 
     buffer.assert_maps_synchronized("After adapter::create_annealing_buffer");
 
-    let output = annealing_adapter.buffer_to_output(&buffer);
+    let mut output = annealing_adapter.buffer_to_output(&buffer);
+    output.set_score(score);
+    output.set_soft_constraints(plan_input.soft_constraints.clone());
+    output.set_hard_constraints(plan_input.hard_constraints.clone());
+    output.set_annealing(settings);
+    output.set_annealing_run(run);
+
+    let mut statistics = BufferStatistics::new(parameters);
+    statistics.emplace_of_buffer(&buffer);
+    output.set_energy_breakdown(annealing_adapter.energy_report(&statistics));
+    output.set_hard_violations(annealing_adapter.hard_violation_report(&statistics));
 
     use std::io::prelude::*;
     File::create("output.json")
         .unwrap()
         .write_all(serde_json::to_string_pretty(&output).unwrap().as_bytes())
         .unwrap();
+
+    println!("\nZapisano {} zajęć do output.json", output.len());
+    println!(
+        "Najlepszy plan po {} z {} przyjętych mutacji",
+        run.best_iteration, run.iterations
+    );
+    if !temperature_given {
+        println!(
+            "Wyznaczona temperatura początkowa: {}",
+            run.initial_temperature
+        );
+    }
+    if run.reheats > 0 {
+        println!("Podgrzano {} razy", run.reheats);
+    }
+    for component in &output.energy_breakdown {
+        println!(
+            "{}: {} (energia {})",
+            component.name, component.total, component.energy
+        );
+    }
+    if !score.is_feasible() {
+        println!(
+            "Plan narusza twarde ograniczenia ({}), nie umieszczono {} zajęć",
+            score.hard,
+            output.unplaced_lessons.len()
+        );
+        for component in output.hard_violations.iter().filter(|x| x.total > 0) {
+            println!("{}: {}", component.name, component.total);
+        }
+    }
 }
@@ -1,24 +1,104 @@
-use crate::data_types::SimpleDate;
+use crate::{
+    annealing::{annealing_buffer::AnnealingRun, energy::Score},
+    data_types::{
+        AnnealingSettings, CalendarDate, ClockTime, HardConstraints, SoftConstraints, Weekday,
+    },
+};
 use serde::Serialize;
 
 #[derive(Serialize)]
 pub struct LessonOwned {
-    pub group: String,
+    pub groups: Vec<String>,
     pub teacher: String,
     pub classroom: String,
     pub subject_name: String,
-    pub time: SimpleDate,
+    pub session: u8,
+    pub day: u8, // Dzień od początku zjazdu
+    pub weekday: Weekday,
+    // Tylko jeśli w danych wejściowych podano daty zjazdów
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<CalendarDate>,
+    pub start: ClockTime,
+    pub end: ClockTime,
+    pub duration: u8, // W jednostkach czasu
+    // Termin przypięty w danych wejściowych
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+}
+
+// Zajęcia, dla których nie znaleziono terminu
+#[derive(Serialize)]
+pub struct UnplacedLesson {
+    pub groups: Vec<String>,
+    pub subject_name: String,
+}
+
+// Grupa, nauczyciel, sala albo kurs (przedmiot danych grup), któremu naliczono
+// karę, i dzień, którego ona dotyczy. Kara za preferencję czasową wskazuje jej
+// numer na liście `time_preferences` w danych wejściowych.
+#[derive(Serialize, Default)]
+pub struct EnergyEntry {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teacher: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classroom: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_preference: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub day: Option<u8>,
+    pub penalty: i64,
+    // Kara przemnożona przez wagę grupy, nauczyciela albo ogólną
+    pub energy: f32,
+}
+
+// Składowa energii: `energy` to suma kar przemnożonych przez wagi. Grupy
+// i nauczyciele mogą mieć wagi inne niż ogólna `weight`.
+#[derive(Serialize)]
+pub struct EnergyComponent {
+    pub name: String,
+    pub weight: f32,
+    pub total: i64,
+    pub energy: f32,
+    pub entries: Vec<EnergyEntry>,
 }
 
 #[derive(Serialize)]
 pub struct PlanOutput {
-    lessons: Vec<LessonOwned>,
+    // Plan z naruszeniami twardych ograniczeń (`score.hard > 0`) nie nadaje się do użycia
+    pub feasible: bool,
+    pub score: Score,
+    pub lessons: Vec<LessonOwned>,
+    pub unplaced_lessons: Vec<UnplacedLesson>,
+    pub energy_breakdown: Vec<EnergyComponent>,
+    // Naruszenia ograniczeń liczby zajęć, wliczone w `score.hard`
+    pub hard_violations: Vec<EnergyComponent>,
+    // Użyte ustawienia miękkich ograniczeń, łącznie z domyślnymi
+    pub soft_constraints: SoftConstraints,
+    pub hard_constraints: HardConstraints,
+    // Użyte ustawienia wyżarzania, z wyznaczoną temperaturą początkową
+    pub annealing: AnnealingSettings,
+    pub annealing_run: AnnealingRun,
 }
 
 impl PlanOutput {
     pub fn new() -> Self {
         Self {
+            feasible: true,
+            score: Score::default(),
             lessons: Vec::new(),
+            unplaced_lessons: Vec::new(),
+            energy_breakdown: Vec::new(),
+            hard_violations: Vec::new(),
+            soft_constraints: SoftConstraints::default(),
+            hard_constraints: HardConstraints::default(),
+            annealing: AnnealingSettings::default(),
+            annealing_run: AnnealingRun::default(),
         }
     }
 
@@ -26,6 +106,39 @@ impl PlanOutput {
         self.lessons.push(lesson_owned);
     }
 
+    pub fn push_unplaced_lesson(&mut self, unplaced_lesson: UnplacedLesson) {
+        self.unplaced_lessons.push(unplaced_lesson);
+    }
+
+    pub fn set_energy_breakdown(&mut self, energy_breakdown: Vec<EnergyComponent>) {
+        self.energy_breakdown = energy_breakdown;
+    }
+
+    pub fn set_hard_violations(&mut self, hard_violations: Vec<EnergyComponent>) {
+        self.hard_violations = hard_violations;
+    }
+
+    pub fn set_soft_constraints(&mut self, soft_constraints: SoftConstraints) {
+        self.soft_constraints = soft_constraints;
+    }
+
+    pub fn set_hard_constraints(&mut self, hard_constraints: HardConstraints) {
+        self.hard_constraints = hard_constraints;
+    }
+
+    pub fn set_annealing(&mut self, annealing: AnnealingSettings) {
+        self.annealing = annealing;
+    }
+
+    pub fn set_annealing_run(&mut self, annealing_run: AnnealingRun) {
+        self.annealing_run = annealing_run;
+    }
+
+    pub fn set_score(&mut self, score: Score) {
+        self.feasible = score.is_feasible();
+        self.score = score;
+    }
+
     pub fn len(&self) -> usize {
         self.lessons.len()
     }
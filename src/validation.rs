@@ -0,0 +1,645 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    data_types::{SlotTable, Subject},
+    input::PlanInput,
+};
+
+// Indeksy grup, nauczycieli i sal są w trakcie wyżarzania zapisywane jako u8
+const MAX_ENTITIES: usize = u8::MAX as usize + 1;
+
+// Problem w danych wejściowych wykryty przed rozpoczęciem układania planu
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidationIssue {
+    // Błędy, przy których nie da się ułożyć planu
+    NoDays,
+    TooManySessions(usize),
+    InvalidTimeGrid(String),
+    InvalidAnnealingSettings(String),
+    TooMany {
+        what: &'static str,
+        count: usize,
+    },
+    DuplicateName {
+        what: &'static str,
+        name: String,
+    },
+    UnknownSubject {
+        groups: String,
+        subject: String,
+    },
+    UnknownStudentGroup {
+        group: String,
+        subject: String,
+    },
+    NoQualifiedTeacher(String),
+    NoSuitableClassroom(String),
+    ClassroomsTooSmall {
+        groups: String,
+        subject: String,
+        students: i32,
+        capacity: i32,
+    },
+    LessonTooLong {
+        subject: String,
+        duration: u8,
+        longest_day: usize,
+    },
+    GroupOverloaded {
+        group: String,
+        required: usize,
+        available: usize,
+    },
+    TeacherOverloaded {
+        teacher: String,
+        required: usize,
+        available: usize,
+    },
+    ClassroomsOverloaded {
+        required: usize,
+        available: usize,
+    },
+    // Ostrzeżenia, plan da się ułożyć, ale dane wyglądają na niekompletne
+    UnusedSubject(String),
+    NoLessons(String),
+    UnknownTaughtSubject {
+        teacher: String,
+        subject: String,
+    },
+    IdleTeacher(String),
+    ClassroomWithoutKinds(String),
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoDays => write!(f, "Zjazd musi mieć co najmniej jeden dzień"),
+            Self::TooManySessions(count) => write!(
+                f,
+                "Zbyt wiele zjazdów: {} (maksymalnie {})",
+                count,
+                u8::MAX
+            ),
+            Self::InvalidTimeGrid(msg) => write!(f, "Niepoprawny podział dnia: {}", msg),
+            Self::InvalidAnnealingSettings(msg) => {
+                write!(f, "Niepoprawne ustawienie wyżarzania: {}", msg)
+            }
+            Self::TooMany { what, count } => write!(
+                f,
+                "Zbyt wiele {}: {} (maksymalnie {})",
+                what, count, MAX_ENTITIES
+            ),
+            Self::DuplicateName { what, name } => {
+                write!(f, "Powtórzona nazwa {}: {}", what, name)
+            }
+            Self::UnknownSubject { groups, subject } => {
+                write!(f, "Grupa {} ma nieznany przedmiot: {}", groups, subject)
+            }
+            Self::UnknownStudentGroup { group, subject } => write!(
+                f,
+                "Nieznana grupa studencka {} w zajęciach z przedmiotu: {}",
+                group, subject
+            ),
+            Self::NoQualifiedTeacher(subject) => write!(
+                f,
+                "Żaden nauczyciel nie może prowadzić przedmiotu: {}",
+                subject
+            ),
+            Self::NoSuitableClassroom(subject) => write!(
+                f,
+                "Żadna sala nie ma rodzaju i wyposażenia wymaganego przez przedmiot: {}",
+                subject
+            ),
+            Self::ClassroomsTooSmall {
+                groups,
+                subject,
+                students,
+                capacity,
+            } => write!(
+                f,
+                "Grupa {} ({} osób) nie mieści się w żadnej sali dla przedmiotu {} \
+                 (największa ma {} miejsc)",
+                groups, students, subject, capacity
+            ),
+            Self::LessonTooLong {
+                subject,
+                duration,
+                longest_day,
+            } => write!(
+                f,
+                "Zajęcia z przedmiotu {} trwają {} jednostek czasu, a najdłuższy dzień ma {}",
+                subject, duration, longest_day
+            ),
+            Self::GroupOverloaded {
+                group,
+                required,
+                available,
+            } => write!(
+                f,
+                "Grupa {} ma {} jednostek czasu zajęć, a plan ma ich tylko {}",
+                group, required, available
+            ),
+            Self::TeacherOverloaded {
+                teacher,
+                required,
+                available,
+            } => write!(
+                f,
+                "Tylko nauczyciel {} może prowadzić {} jednostek czasu zajęć, a plan ma ich tylko {}",
+                teacher, required, available
+            ),
+            Self::ClassroomsOverloaded {
+                required,
+                available,
+            } => write!(
+                f,
+                "Wszystkie zajęcia zajmują {} jednostek czasu, a sale są dostępne tylko przez {}",
+                required, available
+            ),
+            Self::UnusedSubject(subject) => {
+                write!(f, "Przedmiot {} nie ma żadnych zajęć", subject)
+            }
+            Self::NoLessons(group) => write!(f, "Grupa {} nie ma żadnych zajęć", group),
+            Self::UnknownTaughtSubject { teacher, subject } => write!(
+                f,
+                "Nauczyciel {} może prowadzić nieznany przedmiot: {}",
+                teacher, subject
+            ),
+            Self::IdleTeacher(teacher) => {
+                write!(f, "Nauczyciel {} nie może prowadzić żadnych zajęć", teacher)
+            }
+            Self::ClassroomWithoutKinds(classroom) => write!(
+                f,
+                "Sala {} nie ma podanych rodzajów zajęć, więc mogą się w niej odbywać wszystkie",
+                classroom
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub errors: Vec<ValidationIssue>,
+    pub warnings: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+// Zajęcia jednego przedmiotu dla jednej grupy albo kilku grup naraz
+struct Course<'a> {
+    groups: Vec<usize>,
+    subject: &'a Subject,
+}
+
+// Sprawdza dane wejściowe przed układaniem planu. Błędy to sytuacje, w których
+// układanie zakończyłoby się błędem albo nie mogłoby umieścić wszystkich zajęć,
+// ostrzeżenia wskazują dane, które prawdopodobnie są pomyłką.
+pub fn validate(plan_input: &PlanInput) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let errors = &mut report.errors;
+    let warnings = &mut report.warnings;
+
+    if plan_input.days == 0 {
+        errors.push(ValidationIssue::NoDays);
+    }
+    if plan_input.sessions.len() > u8::MAX as usize {
+        errors.push(ValidationIssue::TooManySessions(plan_input.sessions.len()));
+    }
+    let slots = match SlotTable::new(
+        &plan_input.time_grid,
+        plan_input.days,
+        plan_input.session_count(),
+    ) {
+        Ok(slots) => slots,
+        Err(msg) => {
+            errors.push(ValidationIssue::InvalidTimeGrid(msg));
+            SlotTable::default()
+        }
+    };
+    let annealing = &plan_input.annealing;
+    let is_positive = |x: f32| x.is_finite() && x > 0.0;
+    if !is_positive(annealing.final_temperature) {
+        errors.push(ValidationIssue::InvalidAnnealingSettings(format!(
+            "final_temperature = {}",
+            annealing.final_temperature
+        )));
+    }
+    if let Some(initial_temperature) = annealing.initial_temperature {
+        if !is_positive(initial_temperature) {
+            errors.push(ValidationIssue::InvalidAnnealingSettings(format!(
+                "initial_temperature = {}",
+                initial_temperature
+            )));
+        }
+    }
+    let limits = [
+        ("time_limit", annealing.time_limit),
+        ("unimproved_time_limit", annealing.unimproved_time_limit),
+    ];
+    for (name, limit) in limits.iter() {
+        match limit {
+            Some(seconds) if !is_positive(*seconds) => errors.push(
+                ValidationIssue::InvalidAnnealingSettings(format!("{} = {}", name, seconds)),
+            ),
+            _ => (),
+        }
+    }
+    if !is_positive(annealing.reheat_fraction) {
+        errors.push(ValidationIssue::InvalidAnnealingSettings(format!(
+            "reheat_fraction = {}",
+            annealing.reheat_fraction
+        )));
+    }
+    if annealing.stagnation_limit == 0 {
+        errors.push(ValidationIssue::InvalidAnnealingSettings(
+            "stagnation_limit = 0".to_owned(),
+        ));
+    }
+    // Prawdopodobieństwo 0 albo 1 dawałoby zerową albo nieskończoną temperaturę
+    if !(annealing.initial_acceptance > 0.0 && annealing.initial_acceptance < 1.0) {
+        errors.push(ValidationIssue::InvalidAnnealingSettings(format!(
+            "initial_acceptance = {}",
+            annealing.initial_acceptance
+        )));
+    }
+
+    // Bez jednostek czasu sprawdzanie długości zajęć i obciążenia nie ma sensu
+    let has_slots = slots.len() > 0;
+
+    let mut check_names = |what_many, what_one, names: Vec<&str>| {
+        if names.len() > MAX_ENTITIES {
+            errors.push(ValidationIssue::TooMany {
+                what: what_many,
+                count: names.len(),
+            });
+        }
+        let mut seen = HashSet::new();
+        for name in names {
+            if !seen.insert(name) {
+                errors.push(ValidationIssue::DuplicateName {
+                    what: what_one,
+                    name: name.to_owned(),
+                });
+            }
+        }
+    };
+    check_names(
+        "grup studenckich",
+        "grupy",
+        plan_input
+            .student_groups
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect(),
+    );
+    check_names(
+        "nauczycieli",
+        "nauczyciela",
+        plan_input
+            .teachers
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect(),
+    );
+    check_names(
+        "sal",
+        "sali",
+        plan_input
+            .classrooms
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect(),
+    );
+    check_names(
+        "przedmiotów",
+        "przedmiotu",
+        plan_input
+            .subjects
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect(),
+    );
+
+    // Kursy ze znanymi przedmiotami i grupami
+    let mut courses = Vec::new();
+    for (group_id, student_group) in plan_input.student_groups.iter().enumerate() {
+        for subject_name in &student_group.subjects {
+            match plan_input.find_subject(subject_name) {
+                Some(subject) => courses.push(Course {
+                    groups: vec![group_id],
+                    subject,
+                }),
+                None => errors.push(ValidationIssue::UnknownSubject {
+                    groups: student_group.name.clone(),
+                    subject: subject_name.clone(),
+                }),
+            }
+        }
+    }
+    for shared_lesson in &plan_input.shared_lessons {
+        let mut groups = Vec::new();
+        for name in &shared_lesson.groups {
+            match plan_input
+                .student_groups
+                .iter()
+                .position(|x| &x.name == name)
+            {
+                Some(group_id) => groups.push(group_id),
+                None => errors.push(ValidationIssue::UnknownStudentGroup {
+                    group: name.clone(),
+                    subject: shared_lesson.subject.clone(),
+                }),
+            }
+        }
+        match plan_input.find_subject(&shared_lesson.subject) {
+            Some(subject) => courses.push(Course { groups, subject }),
+            None => errors.push(ValidationIssue::UnknownSubject {
+                groups: shared_lesson.groups.join("+"),
+                subject: shared_lesson.subject.clone(),
+            }),
+        }
+    }
+
+    // Nauczyciele i sale dla każdego używanego przedmiotu
+    let qualified_teachers = |subject: &Subject| {
+        (0..plan_input.teachers.len())
+            .filter(|&x| plan_input.teachers[x].can_teach.contains(&subject.name))
+            .collect::<Vec<_>>()
+    };
+    let longest_day = (0..plan_input.days)
+        .map(|day| plan_input.time_grid.slot_starts_of_day(day).len())
+        .max()
+        .unwrap_or(0);
+    let mut checked_subjects = HashSet::new();
+    for course in &courses {
+        let subject = course.subject;
+        if !checked_subjects.insert(subject.name.as_str()) {
+            continue;
+        }
+        if qualified_teachers(subject).is_empty() {
+            errors.push(ValidationIssue::NoQualifiedTeacher(subject.name.clone()));
+        }
+        if !plan_input
+            .classrooms
+            .iter()
+            .any(|x| x.can_hold_subject(subject))
+        {
+            errors.push(ValidationIssue::NoSuitableClassroom(subject.name.clone()));
+        }
+        if has_slots && subject.duration as usize > longest_day {
+            errors.push(ValidationIssue::LessonTooLong {
+                subject: subject.name.clone(),
+                duration: subject.duration,
+                longest_day,
+            });
+        }
+    }
+    for course in &courses {
+        let students = course
+            .groups
+            .iter()
+            .map(|&x| plan_input.student_groups[x].size)
+            .sum::<i32>();
+        let capacity = plan_input
+            .classrooms
+            .iter()
+            .filter(|x| x.can_hold_subject(course.subject))
+            .map(|x| x.capacity)
+            .max();
+        match capacity {
+            Some(capacity) if capacity < students => {
+                errors.push(ValidationIssue::ClassroomsTooSmall {
+                    groups: course
+                        .groups
+                        .iter()
+                        .map(|&x| plan_input.student_groups[x].name.as_str())
+                        .collect::<Vec<_>>()
+                        .join("+"),
+                    subject: course.subject.name.clone(),
+                    students,
+                    capacity,
+                })
+            }
+            _ => (),
+        }
+    }
+
+    // Liczba jednostek czasu potrzebnych grupom, nauczycielom i salom. Nauczycielowi
+    // są liczone tylko zajęcia, których nie może prowadzić nikt inny.
+    let available = slots.len();
+    let mut group_load = vec![0; plan_input.student_groups.len()];
+    let mut teacher_load = HashMap::new();
+    let mut total_load = 0;
+    for course in &courses {
+        let load =
+            plan_input.lesson_count(course.subject) * course.subject.duration.max(1) as usize;
+        for &group_id in &course.groups {
+            group_load[group_id] += load;
+        }
+        if let [teacher_id] = qualified_teachers(course.subject)[..] {
+            *teacher_load.entry(teacher_id).or_insert(0) += load;
+        }
+        total_load += load;
+    }
+    for (group_id, &required) in group_load.iter().enumerate() {
+        if has_slots && required > available {
+            errors.push(ValidationIssue::GroupOverloaded {
+                group: plan_input.student_groups[group_id].name.clone(),
+                required,
+                available,
+            });
+        }
+    }
+    for (teacher_id, teacher) in plan_input.teachers.iter().enumerate() {
+        match teacher_load.get(&teacher_id) {
+            Some(&required) if has_slots && required > available => {
+                errors.push(ValidationIssue::TeacherOverloaded {
+                    teacher: teacher.name.clone(),
+                    required,
+                    available,
+                })
+            }
+            _ => (),
+        }
+    }
+    if has_slots && total_load > available * plan_input.classrooms.len() {
+        errors.push(ValidationIssue::ClassroomsOverloaded {
+            required: total_load,
+            available: available * plan_input.classrooms.len(),
+        });
+    }
+
+    // Ostrzeżenia
+    for subject in &plan_input.subjects {
+        let used = courses.iter().any(|x| x.subject.name == subject.name);
+        if !used || plan_input.lesson_count(subject) == 0 {
+            warnings.push(ValidationIssue::UnusedSubject(subject.name.clone()));
+        }
+    }
+    for (group_id, student_group) in plan_input.student_groups.iter().enumerate() {
+        if group_load[group_id] == 0 {
+            warnings.push(ValidationIssue::NoLessons(student_group.name.clone()));
+        }
+    }
+    for teacher in &plan_input.teachers {
+        for subject in &teacher.can_teach {
+            if plan_input.find_subject(subject).is_none() {
+                warnings.push(ValidationIssue::UnknownTaughtSubject {
+                    teacher: teacher.name.clone(),
+                    subject: subject.clone(),
+                });
+            }
+        }
+        if !courses
+            .iter()
+            .any(|x| teacher.can_teach.contains(&x.subject.name))
+        {
+            warnings.push(ValidationIssue::IdleTeacher(teacher.name.clone()));
+        }
+    }
+    // Jeśli część sal ma podane rodzaje zajęć, brak ich w innej jest
+    // prawdopodobnie przeoczeniem
+    if plan_input.classrooms.iter().any(|x| !x.kinds.is_empty()) {
+        for classroom in plan_input.classrooms.iter().filter(|x| x.kinds.is_empty()) {
+            warnings.push(ValidationIssue::ClassroomWithoutKinds(
+                classroom.name.clone(),
+            ));
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_types::{Classroom, SharedLesson, StudentGroup, SubjectType, Teacher};
+
+    fn plan_input() -> PlanInput {
+        PlanInput::new(
+            vec![
+                StudentGroup {
+                    name: "11A".to_owned(),
+                    size: 15,
+                    subjects: vec!["matematyka".to_owned()],
+                },
+                StudentGroup {
+                    name: "11B".to_owned(),
+                    size: 20,
+                    subjects: vec!["matematyka".to_owned()],
+                },
+            ],
+            vec![Teacher::new(
+                "Kowalski".to_owned(),
+                vec!["matematyka".to_owned()],
+            )],
+            vec![Classroom::new("3.15d".to_owned(), 30)],
+            Vec::new(),
+            vec![Subject::new(
+                "matematyka".to_owned(),
+                SubjectType::Cwiczenia,
+                2,
+            )],
+            1,
+        )
+    }
+
+    #[test]
+    fn valid_input_has_no_issues() {
+        let report = validate(&plan_input());
+        assert!(report.is_valid());
+        assert_eq!(report.warnings, Vec::new());
+    }
+
+    #[test]
+    fn missing_references_are_errors() {
+        let mut plan_input = plan_input();
+        plan_input.student_groups[0]
+            .subjects
+            .push("fizyka".to_owned());
+        plan_input.shared_lessons.push(SharedLesson {
+            subject: "matematyka".to_owned(),
+            groups: vec!["11A".to_owned(), "12A".to_owned()],
+        });
+        plan_input.teachers[0].can_teach = vec!["chemia".to_owned()];
+        plan_input.days = 0;
+
+        let report = validate(&plan_input);
+        assert!(report.errors.contains(&ValidationIssue::NoDays));
+        assert!(report.errors.contains(&ValidationIssue::UnknownSubject {
+            groups: "11A".to_owned(),
+            subject: "fizyka".to_owned()
+        }));
+        assert!(report
+            .errors
+            .contains(&ValidationIssue::UnknownStudentGroup {
+                group: "12A".to_owned(),
+                subject: "matematyka".to_owned()
+            }));
+        assert!(report.errors.contains(&ValidationIssue::NoQualifiedTeacher(
+            "matematyka".to_owned()
+        )));
+        assert_eq!(
+            report.warnings,
+            vec![
+                ValidationIssue::UnknownTaughtSubject {
+                    teacher: "Kowalski".to_owned(),
+                    subject: "chemia".to_owned()
+                },
+                ValidationIssue::IdleTeacher("Kowalski".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn capacity_shortfalls_are_errors() {
+        let mut plan_input = plan_input();
+        // Obie grupy razem nie mieszczą się w sali
+        plan_input.shared_lessons.push(SharedLesson {
+            subject: "matematyka".to_owned(),
+            groups: vec!["11A".to_owned(), "11B".to_owned()],
+        });
+        // Grupa 11A ma 3 + 3 zajęcia i mieści się w 6 jednostkach czasu planu,
+        // ale jedyny nauczyciel i jedyna sala potrzebują ich 9
+        plan_input.subjects[0].count = 3;
+        let report = validate(&plan_input);
+        assert_eq!(
+            report.errors,
+            vec![
+                ValidationIssue::ClassroomsTooSmall {
+                    groups: "11A+11B".to_owned(),
+                    subject: "matematyka".to_owned(),
+                    students: 35,
+                    capacity: 30
+                },
+                ValidationIssue::TeacherOverloaded {
+                    teacher: "Kowalski".to_owned(),
+                    required: 9,
+                    available: 6
+                },
+                ValidationIssue::ClassroomsOverloaded {
+                    required: 9,
+                    available: 6
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn classroom_without_kinds_is_warned_about() {
+        let mut plan_input = plan_input();
+        assert_eq!(validate(&plan_input).warnings, Vec::new());
+
+        let mut lab = Classroom::new("lab".to_owned(), 12);
+        lab.kinds = vec![SubjectType::Laboratorium];
+        plan_input.classrooms.push(lab);
+        assert_eq!(
+            validate(&plan_input).warnings,
+            vec![ValidationIssue::ClassroomWithoutKinds("3.15d".to_owned())]
+        );
+    }
+}